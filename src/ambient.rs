@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use rodio::{ChannelCount, SampleRate, Source};
+use serde::{Deserialize, Serialize};
+
+use crate::rng::Rng;
+
+const SAMPLE_RATE: SampleRate = 44100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Noise {
+    White,
+    Pink,
+    Brown,
+    Rain,
+}
+
+impl Noise {
+    pub const ALL: [Noise; 4] = [Noise::White, Noise::Pink, Noise::Brown, Noise::Rain];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Noise::White => "White Noise",
+            Noise::Pink => "Pink Noise",
+            Noise::Brown => "Brown Noise",
+            Noise::Rain => "Rain",
+        }
+    }
+}
+
+/// Endless procedurally generated noise. Two sources built with the same
+/// kind and seed produce identical samples.
+pub struct NoiseSource {
+    kind: Noise,
+    rng: Rng,
+    // pink filter taps (Paul Kellet's refined method)
+    pink: [f32; 7],
+    // brown integrator
+    brown: f32,
+    // rain: low-passed hiss plus decaying droplets
+    hiss: f32,
+    drop_env: f32,
+    drop_tone: f32,
+}
+
+impl NoiseSource {
+    pub fn new(kind: Noise, seed: u64) -> Self {
+        Self {
+            kind,
+            rng: Rng::new(seed),
+            pink: [0.0; 7],
+            brown: 0.0,
+            hiss: 0.0,
+            drop_env: 0.0,
+            drop_tone: 0.0,
+        }
+    }
+
+    fn next_pink(&mut self, white: f32) -> f32 {
+        let b = &mut self.pink;
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let out = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+        out * 0.11
+    }
+
+    fn next_brown(&mut self, white: f32) -> f32 {
+        // Leaky integrator keeps the random walk from drifting off
+        self.brown = (self.brown + 0.02 * white) / 1.02;
+        self.brown * 3.5
+    }
+
+    fn next_rain(&mut self, white: f32) -> f32 {
+        self.hiss += 0.35 * (white - self.hiss);
+
+        // A new droplet roughly every 30ms on average
+        if self.rng.next_u64().is_multiple_of(1300) {
+            self.drop_env = 0.25 + 0.35 * self.rng.next_signed().abs();
+        }
+        self.drop_env *= 0.996;
+        self.drop_tone += 0.6 * (white - self.drop_tone);
+        let droplet = (white - self.drop_tone) * self.drop_env;
+
+        self.hiss * 0.35 + droplet
+    }
+}
+
+impl Iterator for NoiseSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let white = self.rng.next_signed();
+        let sample = match self.kind {
+            Noise::White => white * 0.3,
+            Noise::Pink => self.next_pink(white),
+            Noise::Brown => self.next_brown(white),
+            Noise::Rain => self.next_rain(white),
+        };
+        Some(sample.clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for NoiseSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> ChannelCount {
+        1
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(kind: Noise, seed: u64, secs: u32) -> Vec<f32> {
+        NoiseSource::new(kind, seed)
+            .take((SAMPLE_RATE * secs) as usize)
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_samples() {
+        for kind in Noise::ALL {
+            assert_eq!(samples(kind, 42, 1), samples(kind, 42, 1), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn different_seeds_different_samples() {
+        for kind in Noise::ALL {
+            assert_ne!(samples(kind, 1, 1), samples(kind, 2, 1), "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn every_kind_stays_in_range() {
+        for kind in Noise::ALL {
+            for seed in 0..4 {
+                let peak = samples(kind, seed, 2)
+                    .iter()
+                    .fold(0f32, |peak, s| peak.max(s.abs()));
+                // Below 1.0, so the final clamp never has to cut anything off
+                assert!(peak < 1.0, "{:?} seed {} peaks at {}", kind, seed, peak);
+                assert!(peak > 0.05, "{:?} seed {} is silent", kind, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn brown_noise_does_not_drift() {
+        for seed in 0..4 {
+            let samples = samples(Noise::Brown, seed, 10);
+            for second in samples.chunks(SAMPLE_RATE as usize) {
+                let peak = second.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+                let mean = second.iter().sum::<f32>() / second.len() as f32;
+                assert!(peak < 1.0, "seed {} peaks at {}", seed, peak);
+                assert!(mean.abs() < 0.25, "seed {} drifted to {}", seed, mean);
+            }
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use rodio::cpal::traits::HostTrait;
+use rodio::mixer::{self, Mixer, MixerSource};
+use rodio::{ChannelCount, DeviceTrait, OutputStream, OutputStreamBuilder, SampleRate};
+
+const SOFT_CHANNELS: u16 = 2;
+const SOFT_SAMPLE_RATE: u32 = 44100;
+
+/// Where loshell sends its audio.
+#[derive(Clone, Default)]
+pub enum OutputTarget {
+    /// The system default device.
+    #[default]
+    Default,
+    /// An output device by name, as shown by `--list-devices`.
+    Device(String),
+    /// Runs the whole pipeline in real time but discards the samples.
+    Null,
+    /// Like `Null`, but writes the mix to a WAV file.
+    File(PathBuf),
+}
+
+impl OutputTarget {
+    /// Parses `null`, `file:<path>` or a device name.
+    pub fn parse(spec: Option<&str>) -> Self {
+        match spec.map(str::trim) {
+            None | Some("") | Some("default") => OutputTarget::Default,
+            Some("null") => OutputTarget::Null,
+            Some(spec) => match spec.strip_prefix("file:") {
+                Some(path) => OutputTarget::File(PathBuf::from(path)),
+                None => OutputTarget::Device(spec.to_string()),
+            },
+        }
+    }
+}
+
+pub fn list_devices() -> Vec<String> {
+    let host = rodio::cpal::default_host();
+    match host.output_devices() {
+        Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// An open audio output. Sinks connect to its mixer. Opened once and kept
+/// on the main thread, since the device stream can't move between threads.
+pub struct Output {
+    // Keeps the device open; None for the software outputs
+    _stream: Option<OutputStream>,
+    // Runs the software outputs; None for devices
+    _pump: Option<Pump>,
+    mixer: Mixer,
+    channels: ChannelCount,
+    sample_rate: SampleRate,
+}
+
+impl Output {
+    pub fn open(target: &OutputTarget) -> Result<Self, String> {
+        let mut stream = match target {
+            OutputTarget::Default => {
+                OutputStreamBuilder::open_default_stream().map_err(|e| e.to_string())?
+            }
+            OutputTarget::Device(name) => {
+                let device = rodio::cpal::default_host()
+                    .output_devices()
+                    .map_err(|e| e.to_string())?
+                    .find(|d| d.name().is_ok_and(|n| &n == name))
+                    .ok_or_else(|| format!("output device \"{}\" not found", name))?;
+                OutputStreamBuilder::from_device(device)
+                    .and_then(|b| b.open_stream_or_fallback())
+                    .map_err(|e| e.to_string())?
+            }
+            OutputTarget::Null => return Ok(Self::soft(None)),
+            OutputTarget::File(path) => {
+                let wav =
+                    WavWriter::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                return Ok(Self::soft(Some(wav)));
+            }
+        };
+
+        // rodio prints "Dropping OutputStream" to stderr otherwise, over the TUI
+        stream.log_on_drop(false);
+        let mixer = stream.mixer().clone();
+        Ok(Self {
+            channels: stream.config().channel_count(),
+            sample_rate: stream.config().sample_rate(),
+            _stream: Some(stream),
+            _pump: None,
+            mixer,
+        })
+    }
+
+    fn soft(wav: Option<WavWriter>) -> Self {
+        let (mixer, source) = mixer::mixer(SOFT_CHANNELS, SOFT_SAMPLE_RATE);
+        let stop = Arc::new(AtomicBool::new(false));
+        let pump_stop = stop.clone();
+        let handle = thread::spawn(move || pump(source, wav, &pump_stop));
+
+        Self {
+            _stream: None,
+            _pump: Some(Pump {
+                stop,
+                handle: Some(handle),
+            }),
+            mixer,
+            channels: SOFT_CHANNELS,
+            sample_rate: SOFT_SAMPLE_RATE,
+        }
+    }
+
+    pub fn mixer(&self) -> &Mixer {
+        &self.mixer
+    }
+
+    pub fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+}
+
+/// The thread behind a software output. Dropping it stops the thread,
+/// which finishes the WAV file.
+struct Pump {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for Pump {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Pulls samples out of the software mixer at real-time speed until stopped.
+fn pump(mut source: MixerSource, mut wav: Option<WavWriter>, stop: &AtomicBool) {
+    // 10ms of audio per step
+    let chunk = (SOFT_SAMPLE_RATE / 100) as usize * SOFT_CHANNELS as usize;
+    let started = Instant::now();
+    let mut frames: u64 = 0;
+    let mut steps: u64 = 0;
+
+    while !stop.load(Ordering::SeqCst) {
+        for _ in 0..chunk {
+            let sample = source.next().unwrap_or(0.0);
+            if let Some(w) = wav.as_mut() {
+                let _ = w.write_sample(sample);
+            }
+        }
+        frames += (chunk / SOFT_CHANNELS as usize) as u64;
+        steps += 1;
+
+        // Keep the header valid about once a second in case we're killed
+        if steps.is_multiple_of(100)
+            && let Some(w) = wav.as_mut()
+        {
+            let _ = w.update_header();
+        }
+
+        let due = Duration::from_secs_f64(frames as f64 / SOFT_SAMPLE_RATE as f64);
+        if let Some(wait) = due.checked_sub(started.elapsed()) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+/// Minimal 16-bit PCM WAV writer.
+struct WavWriter {
+    file: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut writer = Self {
+            file: BufWriter::new(File::create(path)?),
+            data_len: 0,
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let channels = SOFT_CHANNELS;
+        let rate = SOFT_SAMPLE_RATE;
+        let block_align = channels * 2;
+        let f = &mut self.file;
+        f.write_all(b"RIFF")?;
+        f.write_all(&(36 + self.data_len).to_le_bytes())?;
+        f.write_all(b"WAVEfmt ")?;
+        f.write_all(&16u32.to_le_bytes())?;
+        f.write_all(&1u16.to_le_bytes())?; // PCM
+        f.write_all(&channels.to_le_bytes())?;
+        f.write_all(&rate.to_le_bytes())?;
+        f.write_all(&(rate * block_align as u32).to_le_bytes())?;
+        f.write_all(&block_align.to_le_bytes())?;
+        f.write_all(&16u16.to_le_bytes())?;
+        f.write_all(b"data")?;
+        f.write_all(&self.data_len.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_sample(&mut self, sample: f32) -> io::Result<()> {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        self.file.write_all(&value.to_le_bytes())?;
+        self.data_len = self.data_len.saturating_add(2);
+        Ok(())
+    }
+
+    fn update_header(&mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
+
+impl Drop for WavWriter {
+    fn drop(&mut self) {
+        let _ = self.update_header();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use std::fs;
+
+    fn temp_wav(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("loshell-{}-{}.wav", name, std::process::id()))
+    }
+
+    fn read_u16(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn read_u32(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn file_output_writes_the_mix() {
+        let path = temp_wav("mix");
+        let output = Output::open(&OutputTarget::File(path.clone())).unwrap();
+        let frames = SOFT_SAMPLE_RATE as usize / 10;
+        let tone = SamplesBuffer::new(SOFT_CHANNELS, SOFT_SAMPLE_RATE, vec![0.5; frames * 2]);
+        output.mixer().add(tone);
+        thread::sleep(Duration::from_millis(300));
+        drop(output);
+
+        let bytes = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(read_u32(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(read_u16(&bytes, 22), SOFT_CHANNELS);
+        assert_eq!(read_u32(&bytes, 24), SOFT_SAMPLE_RATE);
+        assert_eq!(read_u32(&bytes, 40) as usize, bytes.len() - 44);
+
+        let samples: Vec<i16> = bytes[44..]
+            .chunks(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        let start = samples.iter().position(|&s| s != 0).unwrap();
+        let expected = (0.5 * i16::MAX as f32) as i16;
+        assert!(
+            samples[start..start + frames * 2]
+                .iter()
+                .all(|&s| s == expected)
+        );
+        assert!(samples[start + frames * 2..].iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn unwritable_files_are_an_error() {
+        let path = temp_wav("missing").join("out.wav");
+        assert!(Output::open(&OutputTarget::File(path)).is_err());
+    }
+}
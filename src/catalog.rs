@@ -0,0 +1,609 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use ratatui::{
+    Frame,
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+};
+use serde::{Deserialize, Deserializer};
+use stream_download::http::reqwest::{Url, header::HeaderMap};
+
+use crate::net;
+use crate::radio::{Quality, Station};
+use crate::storage::{self, NetworkConfig};
+use crate::theme::Theme;
+
+/// A channel in a SomaFM-style `channels.json`.
+#[derive(Clone, Deserialize)]
+pub struct Channel {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    /// Pipe separated, like `ambient|electronica`.
+    #[serde(default)]
+    pub genre: String,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub listeners: u32,
+    #[serde(default)]
+    pub playlists: Vec<PlaylistRef>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct PlaylistRef {
+    pub url: String,
+    #[serde(default)]
+    pub format: String,
+    /// `highest`, `high` or `low`.
+    #[serde(default)]
+    pub quality: String,
+}
+
+#[derive(Deserialize)]
+struct CatalogFile {
+    channels: Vec<Channel>,
+}
+
+// SomaFM sends listener counts as strings
+fn number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Number(u32),
+        Text(String),
+    }
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Number(n) => n,
+        Value::Text(s) => s.trim().parse().unwrap_or(0),
+    })
+}
+
+impl Channel {
+    pub fn genres(&self) -> impl Iterator<Item = &str> {
+        self.genre
+            .split('|')
+            .map(str::trim)
+            .filter(|g| !g.is_empty())
+    }
+
+    pub fn to_station(&self) -> Option<Station> {
+        let mut variants = BTreeMap::new();
+        for quality in [Quality::Low, Quality::Medium, Quality::High] {
+            let name = match quality {
+                Quality::Low => "low",
+                Quality::Medium => "high",
+                Quality::High => "highest",
+            };
+            // mp3 decodes everywhere, so prefer it over aac
+            let playlist = self
+                .playlists
+                .iter()
+                .filter(|p| p.quality == name)
+                .min_by_key(|p| p.format != "mp3");
+            if let Some(playlist) = playlist {
+                variants.insert(quality, playlist.url.clone());
+            }
+        }
+
+        let url = match variants.remove(&Quality::Medium) {
+            Some(url) => url,
+            None => self.playlists.first()?.url.clone(),
+        };
+        Some(Station {
+            name: self.title.clone(),
+            url,
+            variants,
+            genre: self.genres().next().map(String::from),
+            description: Some(self.description.clone()).filter(|d| !d.is_empty()),
+            ..Default::default()
+        })
+    }
+}
+
+enum Load {
+    Loading,
+    Ready {
+        channels: Vec<Channel>,
+        from_cache: bool,
+    },
+    Failed(String),
+}
+
+/// Reads the catalog from `source`: an http(s) url, a `file://` url or a
+/// plain path. A fresh copy replaces the cache; when the source can't be
+/// read, the cache is used instead.
+fn load(source: &str, network: &NetworkConfig, cache: &Path) -> Load {
+    let fresh = read_source(source, network).and_then(|body| {
+        let channels = parse(&body)?;
+        if let Some(dir) = cache.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(cache, &body);
+        Ok(channels)
+    });
+
+    match fresh {
+        Ok(channels) => Load::Ready {
+            channels,
+            from_cache: false,
+        },
+        Err(err) => match fs::read_to_string(cache).map_err(|e| e.to_string()) {
+            Ok(body) => match parse(&body) {
+                Ok(channels) => Load::Ready {
+                    channels,
+                    from_cache: true,
+                },
+                Err(_) => Load::Failed(err),
+            },
+            Err(_) => Load::Failed(err),
+        },
+    }
+}
+
+fn parse(body: &str) -> Result<Vec<Channel>, String> {
+    let file: CatalogFile = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    Ok(file.channels)
+}
+
+fn read_source(source: &str, network: &NetworkConfig) -> Result<String, String> {
+    let url = match Url::parse(source) {
+        Ok(url) if url.scheme() == "file" => url,
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {
+            return fetch(url, network);
+        }
+        _ => return fs::read_to_string(source).map_err(|e| format!("{}: {}", source, e)),
+    };
+    let path = url
+        .to_file_path()
+        .map_err(|_| format!("invalid file url: {}", source))?;
+    fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn fetch(url: Url, network: &NetworkConfig) -> Result<String, String> {
+    let client = net::client(network, HeaderMap::new())?;
+    let rt = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    rt.block_on(async {
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| e.to_string())?;
+        response.text().await.map_err(|e| e.to_string())
+    })
+}
+
+/// Overlay for browsing the catalog and adding channels as stations.
+pub struct Browser {
+    pub visible: bool,
+    load: Arc<Mutex<Load>>,
+    loaded: bool,
+    genre: Option<String>,
+    selected: usize,
+    cache: PathBuf,
+}
+
+impl Browser {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            load: Arc::new(Mutex::new(Load::Loading)),
+            loaded: false,
+            genre: None,
+            selected: 0,
+            cache: storage::catalog_cache_path(),
+        }
+    }
+
+    /// Shows the browser, fetching the catalog in the background the first
+    /// time and again after a failed load.
+    pub fn open(&mut self, source: &str, network: &NetworkConfig) {
+        self.visible = true;
+        if self.loaded && !matches!(*self.load.lock().unwrap(), Load::Failed(_)) {
+            return;
+        }
+        self.loaded = true;
+        *self.load.lock().unwrap() = Load::Loading;
+
+        let load = self.load.clone();
+        let source = source.to_string();
+        let network = network.clone();
+        let cache = self.cache.clone();
+        thread::spawn(move || {
+            let result = self::load(&source, &network, &cache);
+            *load.lock().unwrap() = result;
+        });
+    }
+
+    pub fn close(&mut self) {
+        self.visible = false;
+    }
+
+    /// Channels matching the genre filter, most listened first.
+    fn filtered(&self) -> Vec<Channel> {
+        let load = self.load.lock().unwrap();
+        let Load::Ready { channels, .. } = &*load else {
+            return Vec::new();
+        };
+        let mut channels: Vec<Channel> = channels
+            .iter()
+            .filter(|c| match &self.genre {
+                Some(genre) => c.genres().any(|g| g == genre),
+                None => true,
+            })
+            .cloned()
+            .collect();
+        channels.sort_by_key(|c| Reverse(c.listeners));
+        channels
+    }
+
+    fn genres(&self) -> Vec<String> {
+        let load = self.load.lock().unwrap();
+        let Load::Ready { channels, .. } = &*load else {
+            return Vec::new();
+        };
+        let mut genres: Vec<String> = channels
+            .iter()
+            .flat_map(|c| c.genres().map(String::from).collect::<Vec<_>>())
+            .collect();
+        genres.sort();
+        genres.dedup();
+        genres
+    }
+
+    /// Cycles the genre filter through every genre, then back to all.
+    pub fn next_genre(&mut self) {
+        let genres = self.genres();
+        let next = match &self.genre {
+            None => 0,
+            Some(current) => genres
+                .iter()
+                .position(|g| g == current)
+                .map_or(0, |i| i + 1),
+        };
+        self.genre = genres.get(next).cloned();
+        self.selected = 0;
+    }
+
+    pub fn move_down(&mut self) {
+        let len = self.filtered().len();
+        if len > 0 {
+            self.selected = (self.selected + 1).min(len - 1);
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// The selected channel as a station.
+    pub fn selected_station(&self) -> Option<Station> {
+        self.filtered().get(self.selected)?.to_station()
+    }
+
+    pub fn draw(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        if !self.visible {
+            return;
+        }
+
+        let width = area.width.saturating_sub(8).min(80);
+        let height = area.height.saturating_sub(6).min(24);
+        let browser_area = Rect {
+            x: area.x + (area.width.saturating_sub(width)) / 2,
+            y: area.y + (area.height.saturating_sub(height)) / 2,
+            width,
+            height,
+        };
+
+        let mut lines: Vec<Line> = Vec::new();
+        let genre = self.genre.as_deref().unwrap_or("all");
+        lines.push(Line::from(vec![
+            Span::styled("genre: ", theme.frame()),
+            Span::styled(genre.to_string(), theme.accent()),
+        ]));
+        lines.push(Line::from(""));
+
+        let channels = self.filtered();
+        let from_cache = match &*self.load.lock().unwrap() {
+            Load::Loading => {
+                lines.push(Line::from(Span::styled(
+                    "  loading catalog...",
+                    theme.frame(),
+                )));
+                false
+            }
+            Load::Failed(err) => {
+                lines.push(Line::from(Span::styled(
+                    format!("  couldn't load the catalog: {}", err),
+                    theme.hot(),
+                )));
+                false
+            }
+            Load::Ready { from_cache, .. } => *from_cache,
+        };
+
+        // Keep the selection in view, leaving room for the description
+        let rows = height.saturating_sub(8) as usize;
+        let first = self.selected.saturating_sub(rows.saturating_sub(1));
+        let name_width = (width as usize).saturating_sub(24);
+        for (i, channel) in channels.iter().enumerate().skip(first).take(rows) {
+            let is_selected = i == self.selected;
+            let cursor = if is_selected { "> " } else { "  " };
+            let name: String = channel.title.chars().take(name_width).collect();
+            let padding = name_width.saturating_sub(name.chars().count());
+            lines.push(Line::from(vec![
+                Span::styled(
+                    cursor,
+                    if is_selected {
+                        theme.accent()
+                    } else {
+                        theme.base()
+                    },
+                ),
+                Span::styled(
+                    name,
+                    if is_selected {
+                        theme.accent()
+                    } else {
+                        theme.base()
+                    },
+                ),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(format!("{:>6} listening", channel.listeners), theme.frame()),
+            ]));
+        }
+
+        if let Some(channel) = channels.get(self.selected) {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(
+                channel.description.clone(),
+                theme.frame(),
+            )));
+        }
+
+        let title = if from_cache {
+            " stations (offline, cached) "
+        } else {
+            " stations "
+        };
+        let widget = Paragraph::new(lines)
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .title(Span::styled(title, theme.title()))
+                    .title_bottom(Span::styled(
+                        " j/k move  g genre  enter add  esc close ",
+                        theme.frame(),
+                    ))
+                    .borders(Borders::ALL)
+                    .border_style(theme.frame()),
+            )
+            .style(theme.base());
+        f.render_widget(Clear, browser_area);
+        f.render_widget(widget, browser_area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    const CATALOG: &str = r#"{"channels": [
+        {"title": "Groove Salad", "genre": "ambient|electronica", "listeners": "42",
+         "playlists": [{"url": "http://example.com/gs.pls", "format": "mp3", "quality": "high"}]}
+    ]}"#;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "loshell-catalog-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn playlist(url: &str, format: &str, quality: &str) -> PlaylistRef {
+        PlaylistRef {
+            url: url.to_string(),
+            format: format.to_string(),
+            quality: quality.to_string(),
+        }
+    }
+
+    fn channel(playlists: Vec<PlaylistRef>) -> Channel {
+        Channel {
+            title: "Lush".to_string(),
+            description: String::new(),
+            genre: "electronica|vocals".to_string(),
+            listeners: 0,
+            playlists,
+        }
+    }
+
+    fn titles(load: &Load) -> Vec<&str> {
+        match load {
+            Load::Ready { channels, .. } => channels.iter().map(|c| c.title.as_str()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn channels_become_stations_with_variants() {
+        let station = channel(vec![
+            playlist("low-aac", "aac", "low"),
+            playlist("low-mp3", "mp3", "low"),
+            playlist("high-aac", "aac", "high"),
+            playlist("highest-mp3", "mp3", "highest"),
+        ])
+        .to_station()
+        .unwrap();
+        assert_eq!(station.url, "high-aac");
+        // mp3 wins over aac at the same quality
+        assert_eq!(station.variants.len(), 2);
+        assert_eq!(station.variants[&Quality::Low], "low-mp3");
+        assert_eq!(station.variants[&Quality::High], "highest-mp3");
+        assert_eq!(station.genre.as_deref(), Some("electronica"));
+        assert!(station.description.is_none());
+    }
+
+    #[test]
+    fn channels_without_quality_tags_use_the_first_playlist() {
+        let station = channel(vec![
+            playlist("first", "mp3", ""),
+            playlist("second", "aac", ""),
+        ])
+        .to_station()
+        .unwrap();
+        assert_eq!(station.url, "first");
+        assert!(station.variants.is_empty());
+
+        assert!(channel(Vec::new()).to_station().is_none());
+    }
+
+    #[test]
+    fn files_are_read_by_path_or_url() {
+        let path = temp_path("source");
+        fs::write(&path, CATALOG).unwrap();
+        let network = NetworkConfig::default();
+
+        let url = Url::from_file_path(&path).unwrap().to_string();
+        assert_eq!(read_source(&url, &network).unwrap(), CATALOG);
+        assert_eq!(
+            read_source(path.to_str().unwrap(), &network).unwrap(),
+            CATALOG
+        );
+        let _ = fs::remove_file(&path);
+
+        let error = read_source(path.to_str().unwrap(), &network).unwrap_err();
+        assert!(error.contains("loshell-catalog-source"), "{}", error);
+    }
+
+    #[test]
+    fn a_fresh_catalog_replaces_the_cache() {
+        let cache = temp_path("fresh");
+        let addr = testing::serve(|_, mut stream| {
+            testing::respond(&mut stream, &[], CATALOG.as_bytes());
+        });
+
+        let load = load(
+            &format!("http://{}/", addr),
+            &NetworkConfig::default(),
+            &cache,
+        );
+        assert!(matches!(
+            load,
+            Load::Ready {
+                from_cache: false,
+                ..
+            }
+        ));
+        assert_eq!(titles(&load), ["Groove Salad"]);
+        assert_eq!(fs::read_to_string(&cache).unwrap(), CATALOG);
+        let _ = fs::remove_file(&cache);
+    }
+
+    #[test]
+    fn the_cache_stands_in_when_the_source_fails() {
+        let cache = temp_path("fallback");
+        let network = NetworkConfig::default();
+        let dead = format!("http://{}/", testing::dead_addr());
+        assert!(matches!(load(&dead, &network, &cache), Load::Failed(_)));
+
+        fs::write(&cache, CATALOG).unwrap();
+        let load_from = |source: &str| load(source, &network, &cache);
+        let offline = load_from(&dead);
+        assert!(matches!(
+            offline,
+            Load::Ready {
+                from_cache: true,
+                ..
+            }
+        ));
+        assert_eq!(titles(&offline), ["Groove Salad"]);
+
+        // A catalog that doesn't parse doesn't replace the cache either
+        let addr = testing::serve(|_, mut stream| {
+            testing::respond(&mut stream, &[], b"<html>maintenance</html>");
+        });
+        let broken = load_from(&format!("http://{}/", addr));
+        assert!(matches!(
+            broken,
+            Load::Ready {
+                from_cache: true,
+                ..
+            }
+        ));
+        assert_eq!(fs::read_to_string(&cache).unwrap(), CATALOG);
+        let _ = fs::remove_file(&cache);
+    }
+
+    fn wait_until_loaded(browser: &Browser) {
+        let end = Instant::now() + Duration::from_secs(5);
+        while matches!(*browser.load.lock().unwrap(), Load::Loading) {
+            assert!(Instant::now() < end, "timed out loading the catalog");
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn the_browser_loads_once() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let released = Arc::new(AtomicBool::new(false));
+        let (counter, gate) = (requests.clone(), released.clone());
+        let addr = testing::serve(move |_, mut stream| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            while !gate.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(20));
+            }
+            testing::respond(&mut stream, &[], CATALOG.as_bytes());
+        });
+        let source = format!("http://{}/", addr);
+        let network = NetworkConfig::default();
+        let mut browser = Browser::new();
+        browser.cache = temp_path("browser");
+
+        // Opened again while still loading
+        browser.open(&source, &network);
+        thread::sleep(Duration::from_millis(200));
+        browser.close();
+        browser.open(&source, &network);
+        released.store(true, Ordering::SeqCst);
+        wait_until_loaded(&browser);
+        assert_eq!(browser.filtered().len(), 1);
+
+        // And once it's ready
+        browser.close();
+        browser.open(&source, &network);
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        let _ = fs::remove_file(&browser.cache);
+    }
+
+    #[test]
+    fn the_browser_retries_a_failed_load() {
+        let mut browser = Browser::new();
+        browser.cache = temp_path("browser-retry");
+        let network = NetworkConfig::default();
+        let missing = temp_path("missing");
+
+        browser.open(missing.to_str().unwrap(), &network);
+        wait_until_loaded(&browser);
+        assert!(matches!(*browser.load.lock().unwrap(), Load::Failed(_)));
+
+        fs::write(&missing, CATALOG).unwrap();
+        browser.open(missing.to_str().unwrap(), &network);
+        wait_until_loaded(&browser);
+        assert_eq!(browser.filtered().len(), 1);
+        let _ = fs::remove_file(&missing);
+        let _ = fs::remove_file(&browser.cache);
+    }
+}
@@ -0,0 +1,158 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Broken-down local time.
+#[derive(Clone, Copy)]
+pub struct LocalTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl LocalTime {
+    pub fn from_unix(secs: u64) -> Self {
+        let t = secs as libc::time_t;
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        unsafe {
+            libc::localtime_r(&t, &mut tm);
+        }
+        Self {
+            year: tm.tm_year + 1900,
+            month: (tm.tm_mon + 1) as u32,
+            day: tm.tm_mday as u32,
+            hour: tm.tm_hour as u32,
+            minute: tm.tm_min as u32,
+            second: tm.tm_sec as u32,
+        }
+    }
+
+    pub fn now() -> Self {
+        Self::from_unix(now_secs())
+    }
+
+    /// `2024-05-01 14:03`
+    pub fn date_time(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute
+        )
+    }
+
+    /// Days since 1970-01-01 of this date.
+    pub fn day_number(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day)
+    }
+
+    /// `2024-05-01_14-03-22`, safe to use in file names.
+    pub fn file_stamp(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Days since 1970-01-01 of a calendar date.
+pub fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    // Howard Hinnant's algorithm, with years starting in March
+    let y = year as i64 - (month <= 2) as i64;
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Year, month and day of a day number from `days_from_civil`.
+pub fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = (yoe + era * 400 + (month <= 2) as i64) as i32;
+    (year, month, day)
+}
+
+/// 0 for Monday through 6 for Sunday.
+pub fn weekday(days: i64) -> u32 {
+    // 1970-01-01 was a Thursday
+    (days + 3).rem_euclid(7) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epoch_is_day_zero() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+    }
+
+    #[test]
+    fn leap_days_round_trip() {
+        for (year, month, day) in [
+            (1972, 2, 29),
+            (1900, 2, 28),
+            (1900, 3, 1),
+            (2000, 2, 29),
+            (2024, 2, 29),
+            (2024, 12, 31),
+            (2100, 3, 1),
+        ] {
+            let days = days_from_civil(year, month, day);
+            assert_eq!(civil_from_days(days), (year, month, day));
+        }
+        // 1900 and 2100 aren't leap years, 2000 is
+        assert_eq!(
+            days_from_civil(1900, 3, 1) - days_from_civil(1900, 2, 28),
+            1
+        );
+        assert_eq!(
+            days_from_civil(2000, 3, 1) - days_from_civil(2000, 2, 28),
+            2
+        );
+        assert_eq!(
+            days_from_civil(2100, 3, 1) - days_from_civil(2100, 2, 28),
+            1
+        );
+    }
+
+    #[test]
+    fn every_day_round_trips() {
+        let start = days_from_civil(1960, 1, 1);
+        let end = days_from_civil(2040, 1, 1);
+        let mut previous = civil_from_days(start - 1);
+        for days in start..end {
+            let date = civil_from_days(days);
+            assert_eq!(days_from_civil(date.0, date.1, date.2), days);
+            assert!(date > previous);
+            previous = date;
+        }
+    }
+
+    #[test]
+    fn weekdays() {
+        // A Thursday, a Monday, a leap day Thursday and a Sunday before 1970
+        assert_eq!(weekday(0), 3);
+        assert_eq!(weekday(days_from_civil(2024, 1, 1)), 0);
+        assert_eq!(weekday(days_from_civil(2024, 2, 29)), 3);
+        assert_eq!(weekday(days_from_civil(1969, 12, 28)), 6);
+    }
+}
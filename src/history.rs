@@ -0,0 +1,436 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ratatui::{
+    Frame,
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{self, LocalTime};
+use crate::storage::{self, HistoryData};
+use crate::theme::Theme;
+
+// Oldest plays are dropped past this, favorites are always kept
+const MAX_TRACKS: usize = 2000;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Track {
+    pub station: String,
+    pub title: String,
+    /// Unix seconds.
+    pub played_at: u64,
+    #[serde(default)]
+    pub favorite: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum ExportFormat {
+    Text,
+    Csv,
+}
+
+/// Every track heard, newest last, and an overlay to search them.
+pub struct History {
+    pub visible: bool,
+    pub search_mode: bool,
+    tracks: Vec<Track>,
+    favorites_only: bool,
+    search: String,
+    selected: usize,
+    path: PathBuf,
+}
+
+impl History {
+    pub fn load() -> Self {
+        Self::load_from(&storage::history_path())
+    }
+
+    fn load_from(path: &Path) -> Self {
+        Self {
+            visible: false,
+            search_mode: false,
+            tracks: storage::load_history(path).tracks,
+            favorites_only: false,
+            search: String::new(),
+            selected: 0,
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn save(&self) {
+        storage::save_history(
+            &self.path,
+            &HistoryData {
+                tracks: self.tracks.clone(),
+            },
+        );
+    }
+
+    /// Records the playing track if it changed since the last call.
+    pub fn observe(&mut self, station: &str, title: &str) {
+        if self
+            .tracks
+            .last()
+            .is_some_and(|t| t.station == station && t.title == title)
+        {
+            return;
+        }
+        self.tracks.push(Track {
+            station: station.to_string(),
+            title: title.to_string(),
+            played_at: clock::now_secs(),
+            favorite: false,
+        });
+
+        if self.tracks.len() > MAX_TRACKS {
+            let mut excess = self.tracks.len() - MAX_TRACKS;
+            self.tracks.retain(|t| {
+                let drop = excess > 0 && !t.favorite;
+                if drop {
+                    excess -= 1;
+                }
+                !drop
+            });
+        }
+        self.save();
+    }
+
+    pub fn is_favorite(&self, station: &str, title: &str) -> bool {
+        self.tracks
+            .iter()
+            .any(|t| t.favorite && t.station == station && t.title == title)
+    }
+
+    /// Stars or unstars the playing track. Returns true if it's now a favorite.
+    pub fn toggle_favorite(&mut self, station: &str, title: &str) -> bool {
+        let favorite = !self.is_favorite(station, title);
+        for track in self
+            .tracks
+            .iter_mut()
+            .filter(|t| t.station == station && t.title == title)
+        {
+            track.favorite = favorite;
+        }
+        self.save();
+        favorite
+    }
+
+    pub fn toggle_visible(&mut self) {
+        self.visible = !self.visible;
+        self.search_mode = false;
+        self.selected = 0;
+    }
+
+    pub fn toggle_favorites_only(&mut self) {
+        self.favorites_only = !self.favorites_only;
+        self.selected = 0;
+    }
+
+    pub fn toggle_selected_favorite(&mut self) {
+        if let Some(track) = self.filtered().get(self.selected) {
+            let (station, title) = (track.station.clone(), track.title.clone());
+            self.toggle_favorite(&station, &title);
+        }
+    }
+
+    pub fn enter_search_mode(&mut self) {
+        self.search_mode = true;
+    }
+
+    pub fn exit_search_mode(&mut self) {
+        self.search_mode = false;
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        self.search.push(c);
+        self.selected = 0;
+    }
+
+    pub fn backspace(&mut self) {
+        self.search.pop();
+        self.selected = 0;
+    }
+
+    pub fn move_down(&mut self) {
+        let len = self.filtered().len();
+        if len > 0 {
+            self.selected = (self.selected + 1).min(len - 1);
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Tracks matching the search and view, newest first. A favorite heard
+    /// several times is listed once.
+    fn filtered(&self) -> Vec<&Track> {
+        let search = self.search.to_lowercase();
+        let mut tracks: Vec<&Track> = self
+            .tracks
+            .iter()
+            .rev()
+            .filter(|t| !self.favorites_only || t.favorite)
+            .filter(|t| {
+                search.is_empty()
+                    || t.title.to_lowercase().contains(&search)
+                    || t.station.to_lowercase().contains(&search)
+            })
+            .collect();
+        if self.favorites_only {
+            let mut seen = Vec::new();
+            tracks.retain(|t| {
+                let key = (&t.station, &t.title);
+                let first = !seen.contains(&key);
+                seen.push(key);
+                first
+            });
+        }
+        tracks
+    }
+
+    /// The listed tracks as a file extension and file contents.
+    fn render(&self, format: ExportFormat) -> (&'static str, String) {
+        let tracks = self.filtered();
+        match format {
+            ExportFormat::Text => {
+                let lines: Vec<String> = tracks
+                    .iter()
+                    .map(|t| {
+                        let star = if t.favorite { " ★" } else { "" };
+                        format!(
+                            "{}  {}  {}{}",
+                            LocalTime::from_unix(t.played_at).date_time(),
+                            t.station,
+                            t.title,
+                            star
+                        )
+                    })
+                    .collect();
+                ("txt", lines.join("\n") + "\n")
+            }
+            ExportFormat::Csv => {
+                let mut body = String::from("played_at,station,title,favorite\n");
+                for t in &tracks {
+                    body.push_str(&format!(
+                        "{},{},{},{}\n",
+                        LocalTime::from_unix(t.played_at).date_time(),
+                        csv_field(&t.station),
+                        csv_field(&t.title),
+                        t.favorite
+                    ));
+                }
+                ("csv", body)
+            }
+        }
+    }
+
+    /// Writes the listed tracks to a file in the data dir and returns its path.
+    pub fn export(&self, format: ExportFormat) -> io::Result<PathBuf> {
+        let (extension, body) = self.render(format);
+        let dir = storage::exports_dir();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!(
+            "history {}.{}",
+            LocalTime::now().file_stamp(),
+            extension
+        ));
+        fs::write(&path, body)?;
+        Ok(path)
+    }
+
+    pub fn draw(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        if !self.visible {
+            return;
+        }
+
+        let width = area.width.saturating_sub(8).min(90);
+        let height = area.height.saturating_sub(6).min(24);
+        let history_area = Rect {
+            x: area.x + (area.width.saturating_sub(width)) / 2,
+            y: area.y + (area.height.saturating_sub(height)) / 2,
+            width,
+            height,
+        };
+
+        let mut lines: Vec<Line> = Vec::new();
+        let cursor = if self.search_mode { "_" } else { "" };
+        lines.push(Line::from(vec![
+            Span::styled("/ ", theme.accent()),
+            Span::styled(self.search.as_str(), theme.base()),
+            Span::styled(cursor, theme.accent()),
+        ]));
+        lines.push(Line::from(""));
+
+        let tracks = self.filtered();
+        if tracks.is_empty() {
+            let empty = if self.favorites_only {
+                "  no favorites yet. press f while a song plays."
+            } else {
+                "  nothing heard yet."
+            };
+            lines.push(Line::from(Span::styled(empty, theme.frame())));
+        }
+
+        let rows = height.saturating_sub(5) as usize;
+        let first = self.selected.saturating_sub(rows.saturating_sub(1));
+        let title_width = (width as usize).saturating_sub(42);
+        for (i, track) in tracks.iter().enumerate().skip(first).take(rows) {
+            let is_selected = i == self.selected;
+            let style = if is_selected {
+                theme.accent()
+            } else {
+                theme.base()
+            };
+            let title: String = track.title.chars().take(title_width).collect();
+            let padding = title_width.saturating_sub(title.chars().count());
+            let station: String = track.station.chars().take(16).collect();
+            lines.push(Line::from(vec![
+                Span::styled(if is_selected { "> " } else { "  " }, style),
+                Span::styled(if track.favorite { "★ " } else { "  " }, theme.hot()),
+                Span::styled(title, style),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(format!(" {:<16} ", station), theme.frame()),
+                Span::styled(
+                    LocalTime::from_unix(track.played_at).date_time(),
+                    theme.frame(),
+                ),
+            ]));
+        }
+
+        let title = if self.favorites_only {
+            " favorites "
+        } else {
+            " history "
+        };
+        let widget = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .title(Span::styled(title, theme.title()))
+                    .title_bottom(Span::styled(
+                        " / search  tab favorites  f star  c csv  x text  esc close ",
+                        theme.frame(),
+                    ))
+                    .borders(Borders::ALL)
+                    .border_style(theme.frame()),
+            )
+            .style(theme.base());
+        f.render_widget(Clear, history_area);
+        f.render_widget(widget, history_area);
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty history saved to its own temp file.
+    fn history(name: &str) -> History {
+        let path = std::env::temp_dir().join(format!(
+            "loshell-history-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        History::load_from(&path)
+    }
+
+    fn titles(history: &History) -> Vec<&str> {
+        history
+            .filtered()
+            .iter()
+            .map(|t| t.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn a_track_is_recorded_once_while_it_plays() {
+        let mut history = history("observe");
+        history.observe("Lush", "Song A");
+        history.observe("Lush", "Song A");
+        history.observe("Lush", "Song B");
+        // Same title on another station, then heard again later
+        history.observe("Drone Zone", "Song B");
+        history.observe("Lush", "Song A");
+        assert_eq!(titles(&history), ["Song A", "Song B", "Song B", "Song A"]);
+        let _ = fs::remove_file(&history.path);
+    }
+
+    #[test]
+    fn favorites_are_toggled_and_saved() {
+        let mut history = history("favorites");
+        history.observe("Lush", "Song A");
+        history.observe("Lush", "Song B");
+        history.observe("Lush", "Song A");
+
+        assert!(history.toggle_favorite("Lush", "Song A"));
+        assert!(history.is_favorite("Lush", "Song A"));
+        assert!(!history.is_favorite("Drone Zone", "Song A"));
+
+        let reloaded = History::load_from(&history.path);
+        assert!(reloaded.is_favorite("Lush", "Song A"));
+        assert!(!reloaded.is_favorite("Lush", "Song B"));
+        // Every play is starred, but it's listed once among favorites
+        assert_eq!(reloaded.tracks.iter().filter(|t| t.favorite).count(), 2);
+        history.toggle_favorites_only();
+        assert_eq!(titles(&history), ["Song A"]);
+
+        assert!(!history.toggle_favorite("Lush", "Song A"));
+        assert!(!History::load_from(&history.path).is_favorite("Lush", "Song A"));
+        let _ = fs::remove_file(&history.path);
+    }
+
+    #[test]
+    fn search_matches_titles_and_stations() {
+        let mut history = history("search");
+        history.observe("Lush", "Night Drive");
+        history.observe("Drone Zone", "Deep Hum");
+        history.observe("Lush", "Morning");
+
+        for c in "NIGHT".chars() {
+            history.type_char(c);
+        }
+        assert_eq!(titles(&history), ["Night Drive"]);
+
+        for _ in 0..5 {
+            history.backspace();
+        }
+        for c in "drone".chars() {
+            history.type_char(c);
+        }
+        assert_eq!(titles(&history), ["Deep Hum"]);
+        let _ = fs::remove_file(&history.path);
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("Artist, Band"), "\"Artist, Band\"");
+        assert_eq!(csv_field("The \"Hit\""), "\"The \"\"Hit\"\"\"");
+
+        let mut history = history("csv");
+        history.observe("Lush", "Artist, Band - \"Hit\"");
+        let (extension, body) = history.render(ExportFormat::Csv);
+        assert_eq!(extension, "csv");
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines[0], "played_at,station,title,favorite");
+        assert!(
+            lines[1].ends_with(",Lush,\"Artist, Band - \"\"Hit\"\"\",false"),
+            "{}",
+            lines[1]
+        );
+        let _ = fs::remove_file(&history.path);
+    }
+}
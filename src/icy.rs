@@ -0,0 +1,246 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+use crate::recorder::Recorder;
+
+// Decoders probe the start of the stream and rewind. This much audio is kept
+// so they can, even though the stream itself can't seek.
+const REWIND_LIMIT: usize = 256 * 1024;
+
+/// Strips Shoutcast/Icecast metadata blocks out of an audio stream.
+///
+/// With `Icy-MetaData: 1`, servers insert a metadata block after every
+/// `icy-metaint` audio bytes: one length byte (times 16) followed by text like
+/// `StreamTitle='Artist - Track';`. The decoder must only see the audio bytes.
+/// The audio bytes are also handed to the recorder.
+pub struct IcyReader<R> {
+    inner: R,
+    metaint: Option<usize>,
+    until_meta: usize,
+    position: u64,
+    // The first audio bytes, replayed after a rewind
+    history: Vec<u8>,
+    title: Arc<Mutex<Option<String>>>,
+    recorder: Arc<Mutex<Recorder>>,
+}
+
+impl<R: Read> IcyReader<R> {
+    pub fn new(
+        inner: R,
+        metaint: Option<usize>,
+        title: Arc<Mutex<Option<String>>>,
+        recorder: Arc<Mutex<Recorder>>,
+    ) -> Self {
+        let metaint = metaint.filter(|&n| n > 0);
+        Self {
+            inner,
+            metaint,
+            until_meta: metaint.unwrap_or(0),
+            position: 0,
+            history: Vec::new(),
+            title,
+            recorder,
+        }
+    }
+
+    /// Reads one metadata block. False if the stream ended before or inside it.
+    fn read_metadata(&mut self) -> io::Result<bool> {
+        let mut len = [0u8; 1];
+        if self.inner.read(&mut len)? == 0 {
+            return Ok(false);
+        }
+        let len = len[0] as usize * 16;
+        if len == 0 {
+            // Empty block, title unchanged
+            return Ok(true);
+        }
+
+        let mut block = vec![0u8; len];
+        match self.inner.read_exact(&mut block) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            result => result?,
+        }
+        if let Some(title) = parse_stream_title(&block) {
+            let title = Some(title).filter(|t| !t.is_empty());
+            self.recorder.lock().unwrap().set_title(title.clone());
+            *self.title.lock().unwrap() = title;
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> IcyReader<R> {
+    fn read_audio(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(metaint) = self.metaint else {
+            return self.inner.read(buf);
+        };
+
+        if self.until_meta == 0 {
+            if !self.read_metadata()? {
+                return Ok(0);
+            }
+            self.until_meta = metaint;
+        }
+
+        let max = buf.len().min(self.until_meta);
+        let n = self.inner.read(&mut buf[..max])?;
+        self.until_meta -= n;
+        Ok(n)
+    }
+}
+
+impl<R: Read> Read for IcyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Replay what was read before a rewind
+        let pos = self.position as usize;
+        if pos < self.history.len() {
+            let n = buf.len().min(self.history.len() - pos);
+            buf[..n].copy_from_slice(&self.history[pos..pos + n]);
+            self.position += n as u64;
+            return Ok(n);
+        }
+
+        let n = self.read_audio(buf)?;
+        if self.history.len() == pos && pos < REWIND_LIMIT {
+            self.history.extend_from_slice(&buf[..n]);
+        }
+        self.position += n as u64;
+        self.recorder.lock().unwrap().write(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<R: Read> Seek for IcyReader<R> {
+    // Live streams are never seekable, but decoders still ask for the
+    // position and rewind after probing the format
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.position),
+            SeekFrom::Start(p) if p == self.position => Ok(self.position),
+            SeekFrom::Start(p) if p < self.history.len() as u64 => {
+                self.position = p;
+                Ok(p)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "cannot seek in a live stream",
+            )),
+        }
+    }
+}
+
+/// Pulls the `StreamTitle` value out of a metadata block.
+pub fn parse_stream_title(block: &[u8]) -> Option<String> {
+    let end = block.iter().position(|&b| b == 0).unwrap_or(block.len());
+    let text = decode_text(&block[..end]);
+
+    let start = text.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &text[start..];
+    // Titles can contain quotes, so look for the field terminator
+    let value = match rest.find("';") {
+        Some(stop) => &rest[..stop],
+        None => rest.trim_end_matches('\''),
+    };
+    Some(value.trim().to_string())
+}
+
+// Servers send UTF-8 or Latin-1 without saying which
+fn decode_text(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::icy;
+    use std::io::Cursor;
+
+    fn title(block: &str) -> Option<String> {
+        parse_stream_title(block.as_bytes())
+    }
+
+    fn reader(data: Vec<u8>, metaint: usize) -> IcyReader<Cursor<Vec<u8>>> {
+        IcyReader::new(
+            Cursor::new(data),
+            Some(metaint),
+            Arc::new(Mutex::new(None)),
+            Arc::new(Mutex::new(Recorder::new())),
+        )
+    }
+
+    #[test]
+    fn titles_can_contain_quotes() {
+        assert_eq!(
+            title("StreamTitle='Guns N' Roses - Don't Cry';StreamUrl='';"),
+            Some("Guns N' Roses - Don't Cry".to_string())
+        );
+        // Some servers leave off the final semicolon
+        assert_eq!(
+            title("StreamTitle='It's Over'"),
+            Some("It's Over".to_string())
+        );
+    }
+
+    #[test]
+    fn blocks_without_a_title_are_ignored() {
+        assert_eq!(title("StreamUrl='http://example.com';"), None);
+        assert_eq!(title(""), None);
+    }
+
+    #[test]
+    fn empty_titles_parse_as_empty() {
+        assert_eq!(title("StreamTitle='';"), Some(String::new()));
+        assert_eq!(title("StreamTitle='  ';\0\0\0"), Some(String::new()));
+    }
+
+    #[test]
+    fn metadata_is_stripped_from_the_audio() {
+        let audio: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let data = icy(
+            &audio,
+            100,
+            &["StreamTitle='One';", "", "StreamTitle='Two';"],
+        );
+        let mut reader = reader(data, 100);
+
+        let mut out = vec![0u8; 250];
+        reader.read_exact(&mut out).unwrap();
+        assert_eq!(reader.title.lock().unwrap().as_deref(), Some("One"));
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, audio);
+        assert_eq!(reader.title.lock().unwrap().as_deref(), Some("Two"));
+    }
+
+    #[test]
+    fn rewinds_to_the_start_after_probing() {
+        let audio: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let mut reader = reader(icy(&audio, 100, &["StreamTitle='One';"]), 100);
+
+        let mut probe = [0u8; 300];
+        reader.read_exact(&mut probe).unwrap();
+        assert_eq!(reader.seek(SeekFrom::Start(0)).unwrap(), 0);
+
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, audio);
+        assert!(reader.seek(SeekFrom::Start(2000)).is_err());
+    }
+
+    #[test]
+    fn stream_ending_at_a_metadata_block_is_a_clean_eof() {
+        let audio = vec![7u8; 100];
+        let mut out = Vec::new();
+        reader(audio.clone(), 100).read_to_end(&mut out).unwrap();
+        assert_eq!(out, audio);
+
+        // Cut off halfway through the block
+        let mut data = icy(&audio, 100, &["StreamTitle='Cut';"]);
+        data.truncate(110);
+        let mut out = Vec::new();
+        reader(data, 100).read_to_end(&mut out).unwrap();
+        assert_eq!(out, audio);
+    }
+}
@@ -1,11 +1,12 @@
 use std::{
     io::{self, Stdout},
+    sync::atomic::{AtomicBool, Ordering},
     time::{Duration, Instant},
 };
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, Event, KeyCode, KeyEvent},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
@@ -14,24 +15,95 @@ use ratatui::{
     backend::CrosstermBackend,
     layout::{Alignment, Rect},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Paragraph, Wrap},
 };
 
+use crate::audio::{Output, OutputTarget};
+use crate::catalog::Browser;
+use crate::history::{ExportFormat, History};
 use crate::pomodoro::{Mode, Pomodoro};
-use crate::radio::Radio;
-use crate::storage::Config;
+use crate::radio::{Radio, RadioState, Sleep, Station};
+use crate::stats::Stats;
+use crate::storage::{Config, Session};
 use crate::theme::Theme;
 use crate::todo::TodoList;
+use crate::ui::keys::{self, KEYS_HEIGHT, KEYS_WIDTH};
 use crate::ui::logo::{self, LOGO_HEIGHT, LOGO_WIDTH};
-
+use crate::visualizer::Visualizer;
+
+mod ambient;
+mod audio;
+mod catalog;
+mod clock;
+mod history;
+mod icy;
+mod local;
+mod loudness;
+mod mixer;
+mod net;
+mod opus;
+mod playlist;
 mod pomodoro;
 mod radio;
+mod recorder;
+mod rng;
+mod stats;
 mod storage;
+#[cfg(test)]
+mod testing;
 mod theme;
 mod todo;
 mod ui;
+mod visualizer;
+
+const USAGE: &str = "\
+usage: loshell [options]
+
+options:
+  --output <device>   play through a device, `null` or `file:<path>.wav`
+  --list-devices      print the available output devices and exit
+  -h, --help          print this help and exit";
+
+// Set on SIGHUP or SIGTERM, e.g. when the terminal window is closed
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_terminate(_: libc::c_int) {
+    TERMINATED.store(true, Ordering::Relaxed);
+}
+
+/// Lets the main loop save and log the session instead of being killed.
+fn catch_termination() {
+    for signal in [libc::SIGHUP, libc::SIGTERM] {
+        unsafe {
+            libc::signal(signal, on_terminate as *const () as libc::sighandler_t);
+        }
+    }
+}
 
 pub fn run() -> Result<()> {
+    let mut output: Option<String> = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => match args.next() {
+                Some(spec) => output = Some(spec),
+                None => bail!("--output needs a value\n\n{}", USAGE),
+            },
+            "--list-devices" => {
+                for name in audio::list_devices() {
+                    println!("{}", name);
+                }
+                return Ok(());
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            other => bail!("unknown argument: {}\n\n{}", other, USAGE),
+        }
+    }
+
+    catch_termination();
     let _ = enable_raw_mode();
     let mut stdout = io::stdout();
 
@@ -40,7 +112,7 @@ pub fn run() -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let res = run_app(&mut terminal);
+    let res = run_app(&mut terminal, output);
 
     let _ = disable_raw_mode();
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
@@ -49,21 +121,88 @@ pub fn run() -> Result<()> {
     res
 }
 
-fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    output: Option<String>,
+) -> Result<()> {
     let tick_rate = Duration::from_millis(200);
     let mut last_tick = Instant::now();
     let mut frame: u64 = 0;
-    let mut pomo = Pomodoro::new();
-    let mut radio = Radio::new();
     let mut todos = TodoList::load();
+    let mut visualizer = Visualizer::new();
+    let mut browser = Browser::new();
+    let mut history = History::load();
+    let mut stats = Stats::new();
     let mut last_second = Instant::now();
 
-    let config = storage::load_config();
+    let mut warnings = Vec::new();
+    let mut config = storage::load_config().unwrap_or_else(|e| {
+        warnings.push(format!(
+            "{}, using defaults (the file is moved to config.json.bak before saving)",
+            e
+        ));
+        Config::default()
+    });
+    warnings.extend(config.validate());
     let mut theme_name = config.theme;
     let mut theme = Theme::from_name(theme_name);
 
-    loop {
-        terminal.draw(|f| {
+    let (stations, station_warnings) = radio::load_stations(&config.stations, config.station_mode);
+    warnings.extend(station_warnings);
+    let mut pomo = Pomodoro::new(&config.pomodoro);
+    // One output for everything, kept open for the whole run
+    let target = OutputTarget::parse(output.or(config.output_device.clone()).as_deref());
+    let output = Output::open(&target);
+    let mut radio = Radio::new(
+        stations,
+        config.radio.clone(),
+        config.network.clone(),
+        config.loudness.clone(),
+        &config.layers,
+        &output,
+    );
+
+    // Pick up where the last run left off
+    let session = config.session.clone();
+    if let Some(name) = &session.station {
+        radio.select(name);
+    }
+    radio.set_volume(session.volume);
+    radio.set_muted(session.muted);
+    todos.visible = session.show_todo;
+    pomo.track_task(todos.active_task);
+    pomo.visible = session.show_pomodoro;
+    visualizer.visible = session.show_visualizer;
+    if config.radio.autoplay {
+        radio.play();
+    }
+
+    let mut show_error = false;
+    let mut show_keys = false;
+
+    // Shown under the logo until the next key press
+    let mut notice: Option<String> = if warnings.is_empty() {
+        None
+    } else {
+        Some(format!("config: {}", warnings.join("; ")))
+    };
+
+    let result = loop {
+        if TERMINATED.load(Ordering::Relaxed) {
+            break Ok(());
+        }
+        if let Some(failure) = radio.tick() {
+            notice = Some(format!(
+                "couldn't tune in to {}: {}",
+                failure.station,
+                failure.error.reason()
+            ));
+        }
+        if let Some(e) = radio.take_recording_error() {
+            notice = Some(format!("recording failed: {}", e));
+        }
+
+        let drawn = terminal.draw(|f| {
             let area = f.area();
             f.render_widget(ratatui::widgets::Clear, area);
 
@@ -83,20 +222,35 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
             };
 
             // Radio station display (at bottom)
-            let (radio_icon, radio_style, status_text) = if radio.is_loading() {
-                let dots = match frame % 4 {
-                    0 => "   ",
-                    1 => ".  ",
-                    2 => ".. ",
-                    _ => "...",
-                };
-                ("◌", theme.frame(), format!(" loading{}", dots))
-            } else if radio.is_playing() {
-                ("♫", theme.accent(), String::new())
-            } else if radio.is_error() {
-                ("✗", theme.hot(), " error".to_string())
-            } else {
-                ("♪", theme.frame(), String::new())
+            let (radio_icon, radio_style, status_text) = match radio.state() {
+                RadioState::Loading => {
+                    let dots = match frame % 4 {
+                        0 => "   ",
+                        1 => ".  ",
+                        2 => ".. ",
+                        _ => "...",
+                    };
+                    ("◌", theme.frame(), format!(" loading{}", dots))
+                }
+                RadioState::Reconnecting {
+                    attempt,
+                    last_error,
+                } => (
+                    "◌",
+                    theme.frame(),
+                    format!(
+                        " reconnecting (attempt {}, {})",
+                        attempt,
+                        last_error.reason()
+                    ),
+                ),
+                RadioState::Playing => ("♫", theme.accent(), String::new()),
+                RadioState::Error(e) => (
+                    "✗",
+                    theme.hot(),
+                    format!(" error: {} (e for details)", e.reason()),
+                ),
+                RadioState::Stopped => ("♪", theme.frame(), String::new()),
             };
 
             let station_area = Rect {
@@ -110,23 +264,144 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
             let bar_width = 12usize;
             let filled = (vol as usize * bar_width) / 100;
             let empty = bar_width - filled;
-            let vol_bar = format!(
-                "  Volume: [{}{}] {}%",
-                "█".repeat(filled),
-                "-".repeat(empty),
-                vol
+            let mut vol_bar = if radio.is_muted() {
+                "  Volume: muted".to_string()
+            } else {
+                format!(
+                    "  Volume: [{}{}] {}%",
+                    "█".repeat(filled),
+                    "-".repeat(empty),
+                    vol
+                )
+            };
+            let station_gain = radio.station_gain();
+            if station_gain != 0.0 {
+                vol_bar.push_str(&format!(" {:+} dB", station_gain));
+            }
+            if radio.is_normalizing() {
+                vol_bar.push_str(" norm");
+            }
+
+            // Mixer channels (above the station line)
+            let mixer_area = Rect {
+                x: area.x + 2,
+                y: area.y + area.height - 4,
+                width: area.width.saturating_sub(4),
+                height: 1,
+            };
+
+            let mut mixer_spans = vec![Span::styled("mix ", theme.frame())];
+            let main_level = if radio.is_muted() {
+                "muted".to_string()
+            } else {
+                format!("{}%", radio.volume())
+            };
+            let channels = std::iter::once(("station", main_level)).chain(
+                radio.mixer.layers.iter().map(|layer| {
+                    let level = if !layer.is_enabled() {
+                        "off".to_string()
+                    } else if layer.is_muted() {
+                        "muted".to_string()
+                    } else {
+                        format!("{}%", layer.volume())
+                    };
+                    (layer.noise.label(), level)
+                }),
             );
+            for (i, (name, level)) in channels.enumerate() {
+                if i == radio.mixer.selected {
+                    mixer_spans.push(Span::styled(
+                        format!(" ▸{} {} ", name, level),
+                        theme.accent(),
+                    ));
+                } else {
+                    mixer_spans.push(Span::styled(
+                        format!("  {} {} ", name, level),
+                        theme.frame(),
+                    ));
+                }
+            }
+            let mixer_line = Paragraph::new(Line::from(mixer_spans)).style(theme.base());
+
+            // Hidden while switching, the title belongs to the old station
+            let track = match radio.now_playing() {
+                Some((station, title)) if station == radio.station().name => {
+                    let star = if history.is_favorite(&station, &title) {
+                        " ★"
+                    } else {
+                        ""
+                    };
+                    format!(" · {}{}", title, star)
+                }
+                _ => String::new(),
+            };
+
+            let rec = match radio.recording() {
+                Some((bytes, secs)) => format!(
+                    "  ● REC {:.1} MB {:02}:{:02}",
+                    bytes as f64 / 1_000_000.0,
+                    secs / 60,
+                    secs % 60
+                ),
+                None => String::new(),
+            };
+
+            let sleep = match radio.sleep() {
+                Some(Sleep::At(end)) => {
+                    let secs = end.saturating_duration_since(Instant::now()).as_secs();
+                    format!("  ☾ {:02}:{:02}", secs / 60, secs % 60)
+                }
+                Some(Sleep::AfterBreak) => "  ☾ after break".to_string(),
+                None => String::new(),
+            };
 
             let station_line = Paragraph::new(Line::from(vec![
                 Span::styled(format!("{} ", radio_icon), radio_style),
-                Span::styled(radio.station().name, theme.hot()),
+                Span::styled(radio.station().name.as_str(), theme.hot()),
+                Span::styled(rec, theme.hot()),
+                Span::styled(track, theme.accent()),
                 Span::styled(status_text, theme.frame()),
                 Span::styled(vol_bar, theme.frame()),
+                Span::styled(sleep, theme.accent()),
             ]))
             .style(theme.base());
 
-            // Pomodoro display (top right)
-            let pomo_width: u16 = 16;
+            // Pomodoro display (top right), as wide as its longest line
+            let (mm, ss) = pomo.mmss();
+            let mode_label = match pomo.mode {
+                Mode::Focus => "FOCUS",
+                Mode::Break => "BREAK",
+                Mode::LongBreak => "LONG BREAK",
+            };
+            let status = if pomo.running { "▶" } else { "⏸" };
+            let mut dots = pomo.cycle_dots();
+            if !dots.is_empty() {
+                dots.push(' ');
+            }
+            let timer_style = if pomo.running {
+                theme.accent()
+            } else {
+                theme.frame()
+            };
+            let pomo_lines = vec![
+                Line::from(Span::styled(format!("{:02}:{:02}", mm, ss), theme.hot())),
+                Line::from(vec![
+                    Span::styled(dots, theme.accent()),
+                    Span::styled(format!("{} ", status), timer_style),
+                    Span::styled(mode_label, theme.title()),
+                ]),
+                Line::from(Span::styled(
+                    pomo.preset.label(&config.pomodoro),
+                    theme.frame(),
+                )),
+            ];
+            let pomo_width = pomo_lines
+                .iter()
+                .map(Line::width)
+                .max()
+                .unwrap_or(0)
+                .max(18)
+                .min(area.width.saturating_sub(2) as usize) as u16;
             let pomo_area = Rect {
                 x: area.x + area.width.saturating_sub(pomo_width + 2),
                 y: area.y + 1,
@@ -143,21 +418,27 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
             };
 
             let pomo_action = if pomo.running { "Pause" } else { "Continue" };
-            let radio_action = if radio.is_playing() || radio.is_loading() {
-                "stop"
-            } else {
-                "play"
-            };
+            let radio_action = if radio.is_active() { "stop" } else { "play" };
 
-            let help = Paragraph::new(Line::from(vec![
+            let mut help_spans = vec![
                 Span::styled("q ", theme.accent()),
                 Span::styled("quit  ", theme.frame()),
+                Span::styled("? ", theme.accent()),
+                Span::styled("keys  ", theme.frame()),
                 Span::styled("s ", theme.accent()),
                 Span::styled(format!("{}  ", radio_action), theme.frame()),
                 Span::styled("←/→ ", theme.accent()),
                 Span::styled("station  ", theme.frame()),
+            ];
+            if radio.station().is_local() {
+                help_spans.push(Span::styled("</> ", theme.accent()));
+                help_spans.push(Span::styled("track  ", theme.frame()));
+            }
+            help_spans.extend([
                 Span::styled("[/] ", theme.accent()),
                 Span::styled("volume  ", theme.frame()),
+                Span::styled("tab ", theme.accent()),
+                Span::styled("mix  ", theme.frame()),
                 Span::styled("t ", theme.accent()),
                 Span::styled("todos  ", theme.frame()),
                 Span::styled("T ", theme.accent()),
@@ -166,8 +447,8 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
                 Span::styled("pomo  ", theme.frame()),
                 Span::styled("space ", theme.accent()),
                 Span::styled(pomo_action, theme.frame()),
-            ]))
-            .style(theme.base());
+            ]);
+            let help = Paragraph::new(Line::from(help_spans)).style(theme.base());
 
             // Todo list in center area
             let todo_area = Rect {
@@ -178,37 +459,84 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
             };
 
             f.render_widget(logo::logo(&theme, theme_name), logo_area);
+            // A pending auto-start takes over the notice line
+            if let Some(msg) = pomo.countdown_message().or(notice.clone()) {
+                let notice_area = Rect {
+                    x: area.x + 2,
+                    y: area.y + 2,
+                    width: area.width.saturating_sub(pomo_width + 6),
+                    height: 1,
+                };
+                let notice_line =
+                    Paragraph::new(Span::styled(msg.as_str(), theme.hot())).style(theme.base());
+                f.render_widget(notice_line, notice_area);
+            }
+            f.render_widget(mixer_line, mixer_area);
             f.render_widget(station_line, station_area);
             f.render_widget(help, help_area);
-            todos.draw(f, todo_area, &theme);
+            if todos.visible {
+                todos.draw(f, todo_area, &theme);
+            } else {
+                visualizer.draw(f, todo_area, &theme);
+            }
 
-            // Pomodoro on top
-            if pomo.visible {
-                let (mm, ss) = pomo.mmss();
-                let mode_label = match pomo.mode {
-                    Mode::Focus => "FOCUS",
-                    Mode::Break => "BREAK",
-                };
-                let status = if pomo.running { "▶" } else { "⏸" };
-                let timer_style = if pomo.running {
-                    theme.accent()
-                } else {
-                    theme.frame()
+            // Full radio error, toggled with e
+            if show_error && let Some(err) = radio.error() {
+                let width = area.width.saturating_sub(8).min(70);
+                let error_area = Rect {
+                    x: area.x + (area.width.saturating_sub(width)) / 2,
+                    y: area.y + area.height / 3,
+                    width,
+                    height: 7,
                 };
-
-                let pomo_widget = Paragraph::new(vec![
-                    Line::from(Span::styled(format!("{:02}:{:02}", mm, ss), theme.hot())),
-                    Line::from(vec![
-                        Span::styled(format!("{} ", status), timer_style),
-                        Span::styled(mode_label, theme.title()),
-                    ]),
+                let error_widget = Paragraph::new(vec![
+                    Line::from(Span::styled(err.reason(), theme.hot())),
+                    Line::from(""),
+                    Line::from(Span::styled(err.message, theme.base())),
                 ])
-                .alignment(Alignment::Right)
+                .wrap(Wrap { trim: true })
+                .block(
+                    Block::default()
+                        .title(Span::styled(" radio error ", theme.title()))
+                        .borders(Borders::ALL)
+                        .border_style(theme.frame()),
+                )
                 .style(theme.base());
+                f.render_widget(ratatui::widgets::Clear, error_area);
+                f.render_widget(error_widget, error_area);
+            }
+
+            browser.draw(f, area, &theme);
+            history.draw(f, area, &theme);
 
+            // Pomodoro on top
+            if pomo.visible {
+                let pomo_widget = Paragraph::new(pomo_lines)
+                    .alignment(Alignment::Right)
+                    .style(theme.base());
                 f.render_widget(pomo_widget, pomo_area);
             }
-        })?;
+
+            // Stats cover everything, pomodoro included
+            stats.draw(f, area, &theme, &todos.tasks);
+
+            // Key list, toggled with ?
+            if show_keys {
+                let width = KEYS_WIDTH.min(area.width);
+                let height = KEYS_HEIGHT.min(area.height);
+                let keys_area = Rect {
+                    x: area.x + (area.width - width) / 2,
+                    y: area.y + (area.height - height) / 2,
+                    width,
+                    height,
+                };
+                f.render_widget(ratatui::widgets::Clear, keys_area);
+                f.render_widget(keys::keys(&theme), keys_area);
+            }
+        });
+        if let Err(e) = drawn {
+            break Err(e.into());
+        }
 
         // remaining time
         let timeout = tick_rate
@@ -216,115 +544,229 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
             .unwrap_or_else(|| Duration::from_secs(0));
 
         // input
-        if event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
+        let key = match read_key(timeout) {
+            Ok(key) => key,
+            Err(e) => break Err(e.into()),
+        };
+        if let Some(key) = key {
+            notice = None;
+
+            if show_error {
+                // Any key closes the error details
+                show_error = false;
+            } else if show_keys {
+                // Any key closes the key list
+                show_keys = false;
+            } else if pomo.countdown.is_some() {
+                if countdown_key(&mut pomo, key.code, &mut notice) {
+                    break Ok(());
+                }
+            } else if stats.visible {
+                // Stats overlay captures all keys
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('S') | KeyCode::Char('q') => stats.close(),
+                    KeyCode::Left => stats.earlier(),
+                    KeyCode::Right => stats.later(),
+                    KeyCode::Tab => stats.next_period(),
+                    _ => {}
+                }
+            } else if history.search_mode {
+                match key.code {
+                    KeyCode::Enter | KeyCode::Esc => history.exit_search_mode(),
+                    KeyCode::Backspace => history.backspace(),
+                    KeyCode::Char(c) => history.type_char(c),
+                    _ => {}
+                }
+            } else if history.visible {
+                // History overlay captures all keys
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('h') | KeyCode::Char('q') => {
+                        history.toggle_visible()
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => history.move_down(),
+                    KeyCode::Char('k') | KeyCode::Up => history.move_up(),
+                    KeyCode::Char('/') => history.enter_search_mode(),
+                    KeyCode::Tab => history.toggle_favorites_only(),
+                    KeyCode::Char('f') => history.toggle_selected_favorite(),
+                    KeyCode::Char('c') => notice = Some(export(&history, ExportFormat::Csv)),
+                    KeyCode::Char('x') => notice = Some(export(&history, ExportFormat::Text)),
+                    _ => {}
+                }
+            } else if browser.visible {
+                // Station browser captures all keys
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('b') | KeyCode::Char('q') => browser.close(),
+                    KeyCode::Char('j') | KeyCode::Down => browser.move_down(),
+                    KeyCode::Char('k') | KeyCode::Up => browser.move_up(),
+                    KeyCode::Char('g') => browser.next_genre(),
+                    KeyCode::Enter => {
+                        if let Some(station) = browser.selected_station() {
+                            notice = Some(add_station(&mut radio, &mut config, station));
+                        }
+                    }
+                    _ => {}
+                }
+            } else if todos.input_mode {
                 // Todo input mode captures all keys
-                if todos.input_mode {
-                    match key.code {
-                        KeyCode::Enter => todos.confirm_input(),
-                        KeyCode::Esc => todos.cancel_input(),
-                        KeyCode::Backspace => todos.backspace(),
-                        KeyCode::Char(c) => todos.type_char(c),
-                        _ => {}
+                match key.code {
+                    KeyCode::Enter => todos.confirm_input(),
+                    KeyCode::Esc => todos.cancel_input(),
+                    KeyCode::Backspace => todos.backspace(),
+                    KeyCode::Char(c) => todos.type_char(c),
+                    _ => {}
+                }
+            } else if todos.visible {
+                // Todo visible - handle todo keys first
+                match key.code {
+                    KeyCode::Char('q') => break Ok(()),
+                    KeyCode::Char('t') => todos.toggle_visible(),
+                    KeyCode::Char('T') => {
+                        theme_name = theme_name.next();
+                        theme = Theme::from_name(theme_name);
+                        config.theme = theme_name;
+                        storage::save_config(&config);
                     }
-                } else if todos.visible {
-                    // Todo visible - handle todo keys first
-                    match key.code {
-                        KeyCode::Char('q') => {
-                            todos.save();
-                            radio.stop();
-                            return Ok(());
+                    KeyCode::Char('n') => todos.enter_input_mode(),
+                    KeyCode::Char('j') => todos.move_down(),
+                    KeyCode::Char('k') => todos.move_up(),
+                    KeyCode::Char('x') => todos.toggle_completed(),
+                    KeyCode::Char('d') => todos.delete_selected(),
+                    KeyCode::Enter => todos.select_for_pomodoro(),
+                    KeyCode::Char('s') => radio.toggle(),
+                    KeyCode::Char('e') => show_error = radio.error().is_some(),
+                    KeyCode::Char('?') => show_keys = true,
+                    KeyCode::Char('R') => radio.toggle_recording(),
+                    KeyCode::Char('z') => radio.cycle_sleep(),
+                    KeyCode::Char('h') => history.toggle_visible(),
+                    KeyCode::Char('S') => stats.open(),
+                    KeyCode::Char('f') => {
+                        if let Some((station, title)) = radio.now_playing() {
+                            history.toggle_favorite(&station, &title);
                         }
-                        KeyCode::Char('t') => todos.toggle_visible(),
-                        KeyCode::Char('T') => {
-                            theme_name = theme_name.next();
-                            theme = Theme::from_name(theme_name);
-                            storage::save_config(&Config { theme: theme_name });
+                    }
+                    KeyCode::Char('b') => browser.open(&config.catalog_url, &config.network),
+                    KeyCode::Char('N') => notice = Some(toggle_normalize(&mut radio, &mut config)),
+                    KeyCode::Char('{') => {
+                        notice = Some(adjust_station_gain(&mut radio, &mut config, -1.0))
+                    }
+                    KeyCode::Char('}') => {
+                        notice = Some(adjust_station_gain(&mut radio, &mut config, 1.0))
+                    }
+                    KeyCode::Left => radio.prev_station(),
+                    KeyCode::Right => radio.next_station(),
+                    KeyCode::Char('>') => radio.next_track(),
+                    KeyCode::Char('<') => radio.prev_track(),
+                    KeyCode::Char(' ') => {
+                        // Auto-track selected task if none tracked
+                        if todos.active_task.is_none() && !todos.tasks.is_empty() {
+                            todos.select_for_pomodoro();
+                            pomo.track_task(todos.active_task);
                         }
-                        KeyCode::Char('n') => todos.enter_input_mode(),
-                        KeyCode::Char('j') => todos.move_down(),
-                        KeyCode::Char('k') => todos.move_up(),
-                        KeyCode::Char('x') => todos.toggle_completed(),
-                        KeyCode::Char('d') => todos.delete_selected(),
-                        KeyCode::Enter => todos.select_for_pomodoro(),
-                        KeyCode::Char('s') => radio.toggle(),
-                        KeyCode::Left => radio.prev_station(),
-                        KeyCode::Right => radio.next_station(),
-                        KeyCode::Char(' ') => {
-                            // Auto-track selected task if none tracked
-                            if todos.active_task.is_none() && !todos.tasks.is_empty() {
-                                todos.select_for_pomodoro();
-                            }
-                            pomo.start_pause();
+                        pomo.start_pause();
+                    }
+                    KeyCode::Char('r') => pomo.stop_reset(),
+                    KeyCode::Char('+') => pomo.add_five_minutes(),
+                    KeyCode::Char('P') => notice = Some(cycle_preset(&mut pomo, &mut config)),
+                    KeyCode::Char('[') => change_volume(&mut radio, &mut config, -10),
+                    KeyCode::Char(']') => change_volume(&mut radio, &mut config, 10),
+                    KeyCode::Tab => radio.mixer.select_next(),
+                    KeyCode::Char('m') => toggle_mute(&mut radio, &mut config),
+                    KeyCode::Char('o') => {
+                        if let Some(layer) = radio.mixer.selected_layer() {
+                            layer.toggle_enabled();
+                            save_layers(&radio, &mut config);
                         }
-                        KeyCode::Char('r') => pomo.stop_reset(),
-                        KeyCode::Char('+') => pomo.add_five_minutes(),
-                        KeyCode::Char('[') => radio.set_volume(radio.volume().saturating_sub(10)),
-                        KeyCode::Char(']') => radio.set_volume((radio.volume() + 10).min(100)),
-                        KeyCode::Esc => todos.toggle_visible(),
-                        _ => {}
                     }
-                } else {
-                    // Normal mode
-                    match key.code {
-                        KeyCode::Char('q') => {
-                            todos.save();
-                            radio.stop();
-                            return Ok(());
+                    KeyCode::Esc => todos.toggle_visible(),
+                    _ => {}
+                }
+            } else {
+                // Normal mode
+                match key.code {
+                    KeyCode::Char('q') => break Ok(()),
+                    KeyCode::Char('t') => todos.toggle_visible(),
+                    KeyCode::Char('T') => {
+                        theme_name = theme_name.next();
+                        theme = Theme::from_name(theme_name);
+                        config.theme = theme_name;
+                        storage::save_config(&config);
+                    }
+                    KeyCode::Char('p') => pomo.toggle_visible(),
+                    KeyCode::Char('v') => visualizer.toggle_visible(),
+                    KeyCode::Char('s') => radio.toggle(),
+                    KeyCode::Char('e') => show_error = radio.error().is_some(),
+                    KeyCode::Char('?') => show_keys = true,
+                    KeyCode::Char('R') => radio.toggle_recording(),
+                    KeyCode::Char('z') => radio.cycle_sleep(),
+                    KeyCode::Char('h') => history.toggle_visible(),
+                    KeyCode::Char('S') => stats.open(),
+                    KeyCode::Char('f') => {
+                        if let Some((station, title)) = radio.now_playing() {
+                            history.toggle_favorite(&station, &title);
                         }
-                        KeyCode::Char('t') => todos.toggle_visible(),
-                        KeyCode::Char('T') => {
-                            theme_name = theme_name.next();
-                            theme = Theme::from_name(theme_name);
-                            storage::save_config(&Config { theme: theme_name });
+                    }
+                    KeyCode::Char('b') => browser.open(&config.catalog_url, &config.network),
+                    KeyCode::Char('N') => notice = Some(toggle_normalize(&mut radio, &mut config)),
+                    KeyCode::Char('{') => {
+                        notice = Some(adjust_station_gain(&mut radio, &mut config, -1.0))
+                    }
+                    KeyCode::Char('}') => {
+                        notice = Some(adjust_station_gain(&mut radio, &mut config, 1.0))
+                    }
+                    KeyCode::Left => radio.prev_station(),
+                    KeyCode::Right => radio.next_station(),
+                    KeyCode::Char('>') => radio.next_track(),
+                    KeyCode::Char('<') => radio.prev_track(),
+                    KeyCode::Char(' ') => pomo.start_pause(),
+                    KeyCode::Char('r') => pomo.stop_reset(),
+                    KeyCode::Char('+') => pomo.add_five_minutes(),
+                    KeyCode::Char('P') => notice = Some(cycle_preset(&mut pomo, &mut config)),
+                    KeyCode::Char('[') => change_volume(&mut radio, &mut config, -10),
+                    KeyCode::Char(']') => change_volume(&mut radio, &mut config, 10),
+                    KeyCode::Tab => radio.mixer.select_next(),
+                    KeyCode::Char('m') => toggle_mute(&mut radio, &mut config),
+                    KeyCode::Char('o') => {
+                        if let Some(layer) = radio.mixer.selected_layer() {
+                            layer.toggle_enabled();
+                            save_layers(&radio, &mut config);
                         }
-                        KeyCode::Char('p') => pomo.toggle_visible(),
-                        KeyCode::Char('s') => radio.toggle(),
-                        KeyCode::Left => radio.prev_station(),
-                        KeyCode::Right => radio.next_station(),
-                        KeyCode::Char(' ') => pomo.start_pause(),
-                        KeyCode::Char('r') => pomo.stop_reset(),
-                        KeyCode::Char('+') => pomo.add_five_minutes(),
-                        KeyCode::Char('[') => radio.set_volume(radio.volume().saturating_sub(10)),
-                        KeyCode::Char(']') => radio.set_volume((radio.volume() + 10).min(100)),
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
+
+            pomo.track_task(todos.active_task);
+            save_session(&radio, &todos, &pomo, &visualizer, &mut config);
+            log_sessions(&mut pomo);
         }
 
         // pomodoro tick (1s)
         if last_second.elapsed() >= Duration::from_secs(1) {
-            // Fade radio volume down when approaching timer end
-            if pomo.running && pomo.remaining.as_secs() <= 3 && radio.is_playing() {
-                let secs = pomo.remaining.as_secs();
-                // Gradually reduce: 3s->60%, 2s->40%, 1s->20%, 0s->10%
-                let vol = match secs {
-                    3 => 60,
-                    2 => 40,
-                    1 => 20,
-                    _ => 10,
-                };
-                radio.set_volume(vol);
+            // Duck the mix so it's fully down when the notification plays
+            if pomo.running
+                && pomo.remaining.as_secs_f32() <= config.duck.fade_secs
+                && radio.is_audible()
+            {
+                radio.duck(&config.duck);
             }
 
             // Track time on active task
-            if pomo.running {
-                if let Some(task_id) = todos.active_task {
-                    todos.add_time(task_id, Duration::from_secs(1));
-                    todos.save_throttled();
-                }
+            if pomo.running
+                && let Some(task_id) = todos.active_task
+            {
+                todos.add_time(task_id, Duration::from_secs(1));
+                todos.save_throttled();
             }
 
             if pomo.tick_1s() {
+                if matches!(pomo.mode, Mode::Focus) {
+                    radio.break_ended();
+                }
                 pomo.notify();
-                pomo.play_notification();
-                // Restore volume after a delay (notification lasts ~1.5s)
-                let vol_handle = radio.volume_handle();
-                std::thread::spawn(move || {
-                    std::thread::sleep(Duration::from_millis(2000));
-                    vol_handle.store(100, std::sync::atomic::Ordering::SeqCst);
-                });
+                if let Ok(output) = &output {
+                    pomo.play_notification(output);
+                }
+                log_sessions(&mut pomo);
             }
             last_second = Instant::now();
         }
@@ -332,7 +774,210 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
         // tick
         if last_tick.elapsed() >= tick_rate {
             frame = frame.wrapping_add(1);
+            if let Some((station, title)) = radio.now_playing() {
+                history.observe(&station, &title);
+            }
+            if visualizer.visible {
+                visualizer.update(radio.scope());
+            }
             last_tick = Instant::now();
         }
+    };
+
+    // Every way out ends here, errors and a closed terminal included
+    todos.save();
+    save_session(&radio, &todos, &pomo, &visualizer, &mut config);
+    pomo.quit();
+    log_sessions(&mut pomo);
+    radio.stop();
+    result
+}
+
+/// The next key press, waiting at most `timeout`.
+fn read_key(timeout: Duration) -> io::Result<Option<KeyEvent>> {
+    if event::poll(timeout)?
+        && let Event::Key(key) = event::read()?
+    {
+        return Ok(Some(key));
+    }
+    Ok(None)
+}
+
+/// Adjusts the volume of the selected mixer channel.
+fn change_volume(radio: &mut Radio, config: &mut Config, delta: i16) {
+    match radio.mixer.selected_layer() {
+        Some(layer) => {
+            layer.set_volume((layer.volume() as i16 + delta).clamp(0, 100) as u8);
+            save_layers(radio, config);
+        }
+        None => radio.set_volume((radio.volume() as i16 + delta).clamp(0, 100) as u8),
+    }
+}
+
+fn toggle_mute(radio: &mut Radio, config: &mut Config) {
+    match radio.mixer.selected_layer() {
+        Some(layer) => {
+            layer.toggle_mute();
+            save_layers(radio, config);
+        }
+        None => radio.toggle_mute(),
+    }
+}
+
+fn export(history: &History, format: ExportFormat) -> String {
+    match history.export(format) {
+        Ok(path) => format!("exported to {}", path.display()),
+        Err(e) => format!("export failed: {}", e),
+    }
+}
+
+/// Adds a station from the browser to the list and the config.
+fn add_station(radio: &mut Radio, config: &mut Config, station: Station) -> String {
+    if radio.has_station(&station.name) {
+        return format!("{} is already in your stations", station.name);
+    }
+    let message = format!("added {}", station.name);
+    if let Ok(entry) = serde_json::to_value(&station) {
+        config.stations.push(entry);
+    }
+    storage::save_config(config);
+    radio.add_station(station);
+    message
+}
+
+fn toggle_normalize(radio: &mut Radio, config: &mut Config) -> String {
+    radio.toggle_normalize();
+    config.loudness = radio.loudness().clone();
+    storage::save_config(config);
+    if radio.is_normalizing() {
+        "loudness normalization on".to_string()
+    } else {
+        "loudness normalization off".to_string()
+    }
+}
+
+fn adjust_station_gain(radio: &mut Radio, config: &mut Config, delta_db: f32) -> String {
+    radio.adjust_station_gain(delta_db);
+    config.loudness = radio.loudness().clone();
+    storage::save_config(config);
+    format!(
+        "{} gain {:+} dB",
+        radio.station().name,
+        radio.station_gain()
+    )
+}
+
+/// Moves to the next pomodoro preset, skipping the custom one if its
+/// lengths are invalid.
+/// Handles a key while an auto-start counts down: `q` quits, space starts
+/// the phase now and any other key holds it. Returns true to quit.
+fn countdown_key(pomo: &mut Pomodoro, code: KeyCode, notice: &mut Option<String>) -> bool {
+    match code {
+        KeyCode::Char('q') => return true,
+        KeyCode::Char(' ') => pomo.start_pause(),
+        _ => {
+            pomo.hold();
+            *notice = Some("timer held, press space to start".to_string());
+        }
+    }
+    false
+}
+
+fn cycle_preset(pomo: &mut Pomodoro, config: &mut Config) -> String {
+    let mut preset = pomo.preset.next();
+    let skipped = pomo.set_preset(preset, &config.pomodoro).err();
+    if skipped.is_some() {
+        // Only custom can be invalid, and classic comes after it
+        preset = preset.next();
+        pomo.set_preset(preset, &config.pomodoro).unwrap();
+    }
+    config.pomodoro.preset = preset;
+    storage::save_config(config);
+
+    let label = preset.label(&config.pomodoro);
+    match skipped {
+        Some(e) => format!("skipped custom preset ({}), using {}", e, label),
+        None => format!("pomodoro preset: {}", label),
+    }
+}
+
+fn save_layers(radio: &Radio, config: &mut Config) {
+    config.layers = radio.mixer.configs();
+    storage::save_config(config);
+}
+
+/// Appends finished pomodoro sessions to the log.
+fn log_sessions(pomo: &mut Pomodoro) {
+    for entry in pomo.take_finished() {
+        storage::append_session(&entry);
+    }
+}
+
+/// Saves the station, volume and panel visibility if any of them changed.
+fn save_session(
+    radio: &Radio,
+    todos: &TodoList,
+    pomo: &Pomodoro,
+    visualizer: &Visualizer,
+    config: &mut Config,
+) {
+    let session = Session {
+        station: Some(radio.station().name.clone()),
+        volume: radio.volume(),
+        muted: radio.is_muted(),
+        show_todo: todos.visible,
+        show_pomodoro: pomo.visible,
+        show_visualizer: visualizer.visible,
+    };
+    if session != config.session {
+        config.session = session;
+        storage::save_config(config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::PomodoroConfig;
+
+    /// A break about to start on its own.
+    fn counting_down() -> Pomodoro {
+        let mut pomo = Pomodoro::new(&PomodoroConfig {
+            auto_start_breaks: true,
+            ..Default::default()
+        });
+        pomo.start_pause();
+        pomo.remaining = Duration::ZERO;
+        pomo.tick_1s();
+        assert!(pomo.countdown.is_some());
+        pomo
+    }
+
+    #[test]
+    fn q_quits_during_the_countdown() {
+        let mut pomo = counting_down();
+        let mut notice = None;
+        assert!(countdown_key(&mut pomo, KeyCode::Char('q'), &mut notice));
+        assert!(notice.is_none());
+    }
+
+    #[test]
+    fn space_starts_the_countdown_phase_now() {
+        let mut pomo = counting_down();
+        let mut notice = None;
+        assert!(!countdown_key(&mut pomo, KeyCode::Char(' '), &mut notice));
+        assert!(pomo.running);
+        assert_eq!(pomo.countdown, None);
+        assert!(notice.is_none());
+    }
+
+    #[test]
+    fn other_keys_hold_the_countdown() {
+        let mut pomo = counting_down();
+        let mut notice = None;
+        assert!(!countdown_key(&mut pomo, KeyCode::Char('s'), &mut notice));
+        assert!(!pomo.running);
+        assert_eq!(pomo.countdown, None);
+        assert!(notice.is_some());
     }
 }
@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use rodio::Decoder;
+
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "m4a", "aac", "flac", "ogg", "oga", "wav"];
+
+/// Expands a leading `~` to the home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| path.to_path_buf()),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Collects playable files under `dir`, including subfolders, sorted by path.
+pub fn scan(dir: &Path) -> Vec<PathBuf> {
+    let mut tracks = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    // Symlinked folders are followed, but each folder is only read once, so
+    // a link back up the tree can't loop
+    let mut visited = HashSet::new();
+
+    while let Some(dir) = pending.pop() {
+        let Ok(real) = fs::canonicalize(&dir) else {
+            continue;
+        };
+        if !visited.insert(real) {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if is_audio_file(&path) {
+                tracks.push(path);
+            }
+        }
+    }
+
+    tracks.sort();
+    tracks
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+pub fn open(path: &Path) -> Result<Decoder<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Decoder::try_from(file).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Display name for a track, the file name without extension.
+pub fn track_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("loshell-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn scan_finds_audio_in_subfolders_in_order() {
+        let dir = temp_dir("scan");
+        fs::create_dir_all(dir.join("b/c")).unwrap();
+        for file in [
+            "b/c/3.flac",
+            "b/2.MP3",
+            "1.wav",
+            "b/cover.jpg",
+            "notes.txt",
+            "noext",
+        ] {
+            fs::write(dir.join(file), b"").unwrap();
+        }
+
+        let tracks: Vec<PathBuf> = scan(&dir)
+            .into_iter()
+            .map(|t| t.strip_prefix(&dir).unwrap().to_path_buf())
+            .collect();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(
+            tracks,
+            ["1.wav", "b/2.MP3", "b/c/3.flac"].map(PathBuf::from)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_survives_a_link_back_up_the_tree() {
+        let dir = temp_dir("scan-loop");
+        fs::create_dir_all(dir.join("a")).unwrap();
+        fs::write(dir.join("a/1.wav"), b"").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("a/up")).unwrap();
+
+        let tracks = scan(&dir);
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(tracks, [dir.join("a/1.wav")]);
+    }
+}
@@ -0,0 +1,189 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicU32, Ordering},
+};
+use std::time::Duration;
+
+use rodio::{ChannelCount, SampleRate, Source, source::SeekError};
+
+// Loudness is averaged over this long
+const MEASURE_TIME: f32 = 3.0;
+// How quickly the gain follows the measurement
+const GAIN_TIME: f32 = 2.0;
+const LIMITER_RELEASE: f32 = 0.1;
+// Quieter than this is a pause between songs, not a quiet song
+const GATE_DB: f32 = -50.0;
+const MAX_BOOST_DB: f32 = 12.0;
+const MAX_CUT_DB: f32 = -12.0;
+const LIMITER_CEILING: f32 = 0.97;
+
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Evens out loudness between stations: a slow automatic gain towards a
+/// target RMS level, followed by a peak limiter so boosts never clip.
+/// The per-station offset applies even with normalization off.
+pub struct Normalize<S> {
+    input: S,
+    enabled: Arc<AtomicBool>,
+    // f32 bits of the station offset in dB, changeable while playing
+    offset_db: Arc<AtomicU32>,
+    offset_bits: u32,
+    offset: f32,
+    target: f32,
+    gate: f32,
+    min_gain: f32,
+    max_gain: f32,
+    mean_square: f32,
+    gain: f32,
+    envelope: f32,
+    measure_coeff: f32,
+    gain_coeff: f32,
+    release_coeff: f32,
+}
+
+impl<S: Source> Normalize<S> {
+    pub fn new(
+        input: S,
+        enabled: Arc<AtomicBool>,
+        offset_db: Arc<AtomicU32>,
+        target_db: f32,
+    ) -> Self {
+        let rate = input.sample_rate() as f32 * input.channels() as f32;
+        let coeff = |secs: f32| 1.0 - (-1.0 / (secs * rate)).exp();
+        let target = db_to_gain(target_db);
+        let offset_bits = offset_db.load(Ordering::Relaxed);
+        Self {
+            measure_coeff: coeff(MEASURE_TIME),
+            gain_coeff: coeff(GAIN_TIME),
+            release_coeff: coeff(LIMITER_RELEASE),
+            input,
+            enabled,
+            offset_db,
+            offset_bits,
+            offset: db_to_gain(f32::from_bits(offset_bits)),
+            target,
+            gate: db_to_gain(GATE_DB),
+            min_gain: db_to_gain(MAX_CUT_DB),
+            max_gain: db_to_gain(MAX_BOOST_DB),
+            // Start as if already on target so the first seconds don't jump
+            mean_square: target * target,
+            gain: 1.0,
+            envelope: 0.0,
+        }
+    }
+}
+
+impl<S: Source> Iterator for Normalize<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let bits = self.offset_db.load(Ordering::Relaxed);
+        if bits != self.offset_bits {
+            self.offset_bits = bits;
+            self.offset = db_to_gain(f32::from_bits(bits));
+        }
+        let sample = self.input.next()? * self.offset;
+
+        self.mean_square += self.measure_coeff * (sample * sample - self.mean_square);
+        let enabled = self.enabled.load(Ordering::Relaxed);
+        let desired = if !enabled {
+            1.0
+        } else if self.mean_square.sqrt() > self.gate {
+            (self.target / self.mean_square.sqrt()).clamp(self.min_gain, self.max_gain)
+        } else {
+            // Hold the gain through silence
+            self.gain
+        };
+        self.gain += self.gain_coeff * (desired - self.gain);
+
+        // Nothing is boosted once the gain has settled back to unity, so
+        // play the station untouched rather than limit its own peaks
+        if !enabled && self.offset_bits == 0 && (self.gain - 1.0).abs() < 1e-3 {
+            self.gain = 1.0;
+            self.envelope = 0.0;
+            return Some(sample);
+        }
+
+        let mut out = sample * self.gain;
+        let peak = out.abs();
+        if peak > self.envelope {
+            self.envelope = peak;
+        } else {
+            self.envelope += self.release_coeff * (peak - self.envelope);
+        }
+        if self.envelope > LIMITER_CEILING {
+            out *= LIMITER_CEILING / self.envelope;
+        }
+        Some(out)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S: Source> Source for Normalize<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.input.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    fn normalize(samples: Vec<f32>, enabled: bool, offset_db: f32) -> Vec<f32> {
+        let input = SamplesBuffer::new(1, 8000, samples);
+        Normalize::new(
+            input,
+            Arc::new(AtomicBool::new(enabled)),
+            Arc::new(AtomicU32::new(offset_db.to_bits())),
+            -18.0,
+        )
+        .collect()
+    }
+
+    #[test]
+    fn off_without_offset_is_untouched() {
+        let samples: Vec<f32> = (0..8000)
+            .map(|n| if n % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        assert_eq!(normalize(samples.clone(), false, 0.0), samples);
+    }
+
+    #[test]
+    fn offset_boosts_are_limited() {
+        let samples = vec![0.9; 8000];
+        let out = normalize(samples, false, 6.0);
+        assert!(out.iter().all(|s| *s <= LIMITER_CEILING + 1e-6));
+    }
+
+    #[test]
+    fn quiet_stations_are_raised_towards_the_target() {
+        let samples: Vec<f32> = (0..8000 * 20)
+            .map(|n| if n % 2 == 0 { 0.02 } else { -0.02 })
+            .collect();
+        let out = normalize(samples, true, 0.0);
+        let last = out.last().unwrap().abs();
+        assert!(last > 0.06 && last <= db_to_gain(-18.0) * 1.05, "{}", last);
+    }
+}
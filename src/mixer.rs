@@ -0,0 +1,155 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rodio::Sink;
+
+use crate::ambient::{Noise, NoiseSource};
+use crate::storage::LayerConfig;
+
+/// A generated ambient sound playing underneath the main station.
+pub struct Layer {
+    pub noise: Noise,
+    volume: u8, // 0-100
+    muted: bool,
+    enabled: bool,
+    // The radio's mix; None without an audio device
+    music: Option<rodio::mixer::Mixer>,
+    sink: Option<Sink>,
+}
+
+impl Layer {
+    fn new(config: &LayerConfig, music: Option<&rodio::mixer::Mixer>) -> Self {
+        let mut layer = Self {
+            noise: config.noise,
+            volume: config.volume.min(100),
+            muted: config.muted,
+            enabled: false,
+            music: music.cloned(),
+            sink: None,
+        };
+        if config.enabled {
+            layer.start();
+        }
+        layer
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, vol: u8) {
+        self.volume = vol.min(100);
+        self.apply_volume();
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.apply_volume();
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// True if the layer can be heard at all.
+    pub fn is_audible(&self) -> bool {
+        self.enabled && !self.muted && self.volume > 0
+    }
+
+    pub fn toggle_enabled(&mut self) {
+        if self.is_enabled() {
+            self.stop();
+        } else {
+            self.start();
+        }
+    }
+
+    fn start(&mut self) {
+        self.enabled = true;
+        let Some(music) = &self.music else {
+            return;
+        };
+        let sink = Sink::connect_new(music);
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        sink.append(NoiseSource::new(self.noise, seed));
+        self.sink = Some(sink);
+        self.apply_volume();
+    }
+
+    fn stop(&mut self) {
+        self.enabled = false;
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+    }
+
+    fn apply_volume(&self) {
+        if let Some(sink) = &self.sink {
+            let vol = if self.muted {
+                0.0
+            } else {
+                self.volume as f32 / 100.0
+            };
+            sink.set_volume(vol);
+        }
+    }
+
+    fn config(&self) -> LayerConfig {
+        LayerConfig {
+            noise: self.noise,
+            volume: self.volume(),
+            muted: self.is_muted(),
+            enabled: self.is_enabled(),
+        }
+    }
+}
+
+/// Channel 0 is the main station (owned by `Radio`), the rest are ambient layers.
+pub struct Mixer {
+    pub selected: usize,
+    pub layers: Vec<Layer>,
+}
+
+impl Mixer {
+    /// Layers play into `music`, the radio's mix, so the duck, the sleep
+    /// fade and the visualizer cover them too.
+    pub fn new(configs: &[LayerConfig], music: Option<&rodio::mixer::Mixer>) -> Self {
+        Self {
+            selected: 0,
+            layers: configs.iter().map(|c| Layer::new(c, music)).collect(),
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % (self.layers.len() + 1);
+    }
+
+    /// The selected ambient layer, or `None` when the main station is selected.
+    pub fn selected_layer(&mut self) -> Option<&mut Layer> {
+        match self.selected {
+            0 => None,
+            n => self.layers.get_mut(n - 1),
+        }
+    }
+
+    pub fn is_audible(&self) -> bool {
+        self.layers.iter().any(Layer::is_audible)
+    }
+
+    /// Turns every layer off.
+    pub fn stop_all(&mut self) {
+        for layer in &mut self.layers {
+            layer.stop();
+        }
+    }
+
+    pub fn configs(&self) -> Vec<LayerConfig> {
+        self.layers.iter().map(Layer::config).collect()
+    }
+}
@@ -0,0 +1,142 @@
+use std::fs;
+use std::time::Duration;
+
+use stream_download::http::reqwest::{self, Certificate, NoProxy, Proxy, header::HeaderMap};
+
+use crate::local;
+use crate::storage::NetworkConfig;
+
+/// Builds the HTTP client used for streams, playlists and the catalog.
+///
+/// Without a proxy in the config, `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY` are
+/// honored. With one, it replaces them for every host outside `no_proxy`,
+/// and those hosts are reached directly. The extra CA bundles are trusted on
+/// top of the system roots.
+pub fn client(config: &NetworkConfig, headers: HeaderMap) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .default_headers(headers)
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+        .read_timeout(Duration::from_secs(config.read_timeout_secs));
+
+    if let Some(url) = config.proxy.as_deref().filter(|u| !u.trim().is_empty()) {
+        let no_proxy = match &config.no_proxy {
+            Some(list) => NoProxy::from_string(list),
+            None => NoProxy::from_env(),
+        };
+        let proxy = Proxy::all(url)
+            .map_err(|e| format!("invalid proxy {}: {}", url, e))?
+            .no_proxy(no_proxy);
+        builder = builder.proxy(proxy);
+    }
+
+    for path in &config.ca_bundles {
+        let path = local::expand_home(path);
+        let pem = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let certs =
+            Certificate::from_pem_bundle(&pem).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if certs.is_empty() {
+            return Err(format!("{}: no certificates found", path.display()));
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use std::sync::mpsc;
+
+    fn get(config: &NetworkConfig, url: &str) -> Result<String, String> {
+        let client = client(config, HeaderMap::new())?;
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let response = client.get(url).send().await.map_err(|e| e.to_string())?;
+            response.text().await.map_err(|e| e.to_string())
+        })
+    }
+
+    /// A proxy that answers every request itself and reports its first line.
+    fn proxy() -> (NetworkConfig, mpsc::Receiver<String>) {
+        let (tx, rx) = mpsc::channel();
+        let addr = testing::serve(move |request, mut stream| {
+            let line = request.head.lines().next().unwrap_or("").to_string();
+            let _ = tx.send(line);
+            testing::respond(&mut stream, &[], b"proxied");
+        });
+        let config = NetworkConfig {
+            proxy: Some(format!("http://{}", addr)),
+            no_proxy: Some("127.0.0.1".to_string()),
+            ..Default::default()
+        };
+        (config, rx)
+    }
+
+    fn with_bundle(path: std::path::PathBuf) -> NetworkConfig {
+        NetworkConfig {
+            ca_bundles: vec![path],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn configured_ca_bundles_are_trusted() {
+        let addr = testing::serve_tls(b"secure");
+        let url = format!("https://localhost:{}/stream", addr.port());
+        assert!(get(&NetworkConfig::default(), &url).is_err());
+        let body = get(&with_bundle(testing::tls_cert_path()), &url).unwrap();
+        assert_eq!(body, "secure");
+    }
+
+    #[test]
+    fn a_missing_ca_bundle_is_an_error() {
+        let path = std::env::temp_dir().join(format!("loshell-missing-{}.pem", std::process::id()));
+        let err = client(&with_bundle(path.clone()), HeaderMap::new()).unwrap_err();
+        assert!(err.starts_with(&path.display().to_string()), "{}", err);
+    }
+
+    #[test]
+    fn an_invalid_ca_bundle_is_an_error() {
+        let path = std::env::temp_dir().join(format!("loshell-invalid-{}.pem", std::process::id()));
+        fs::write(&path, "not a certificate").unwrap();
+        let err = client(&with_bundle(path.clone()), HeaderMap::new()).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(err, format!("{}: no certificates found", path.display()));
+    }
+
+    #[test]
+    fn requests_go_through_the_proxy() {
+        let (config, rx) = proxy();
+        let body = get(&config, "http://radio.invalid/stream").unwrap();
+        assert_eq!(body, "proxied");
+        assert_eq!(
+            rx.recv().unwrap(),
+            "GET http://radio.invalid/stream HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn https_is_tunneled_through_the_proxy() {
+        let (config, rx) = proxy();
+        // The proxy answers instead of opening a tunnel, so TLS fails; only the
+        // CONNECT is checked
+        assert!(get(&config, "https://radio.invalid/stream").is_err());
+        assert_eq!(rx.recv().unwrap(), "CONNECT radio.invalid:443 HTTP/1.1");
+    }
+
+    #[test]
+    fn no_proxy_hosts_are_reached_directly() {
+        let (config, rx) = proxy();
+        let origin = testing::serve(|_, mut stream| {
+            testing::respond(&mut stream, &[], b"direct");
+        });
+        let body = get(&config, &format!("http://{}/stream", origin)).unwrap();
+        assert_eq!(body, "direct");
+        assert!(rx.try_recv().is_err());
+    }
+}
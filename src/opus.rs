@@ -0,0 +1,202 @@
+use std::io::{Read, Seek};
+use std::time::Duration;
+
+use ogg::PacketReader;
+use rodio::{ChannelCount, SampleRate, Source};
+use ropus::{Channels, DecodeMode, Decoder};
+
+// Opus always decodes at 48 kHz, whatever rate the encoder was fed
+const RATE: u32 = 48_000;
+// The longest Opus packet is 120 ms
+const MAX_FRAME: usize = RATE as usize * 120 / 1000;
+
+/// Decodes an Ogg Opus stream, which none of rodio's decoders handle.
+///
+/// Radio servers chain a new logical stream for every song, each starting
+/// with its own `OpusHead`. The decoder is reset for each one and keeps the
+/// channel count of the first, since Opus can decode any packet to mono or
+/// stereo.
+pub struct OpusSource<R: Read + Seek> {
+    packets: PacketReader<R>,
+    decoder: Decoder,
+    channels: ChannelCount,
+    pcm: Vec<f32>,
+    pos: usize,
+    len: usize,
+    // Samples still to drop at the start of the current stream
+    skip: usize,
+}
+
+/// The fields of an `OpusHead` packet that matter for playback.
+struct Head {
+    channels: u8,
+    pre_skip: u16,
+    // Q7.8 dB
+    gain: i16,
+}
+
+impl Head {
+    fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 19 || !data.starts_with(b"OpusHead") {
+            return Err("missing Opus header".to_string());
+        }
+        let head = Self {
+            channels: data[9],
+            pre_skip: u16::from_le_bytes([data[10], data[11]]),
+            gain: i16::from_le_bytes([data[16], data[17]]),
+        };
+        // Family 0 is plain mono or stereo; the others are surround layouts
+        if data[18] != 0 || !(1..=2).contains(&head.channels) {
+            return Err(format!(
+                "{}-channel Opus streams are not supported",
+                head.channels
+            ));
+        }
+        Ok(head)
+    }
+}
+
+impl<R: Read + Seek> OpusSource<R> {
+    /// Reads the stream's first header. Errors if it isn't Ogg Opus or uses
+    /// more than two channels.
+    pub fn new(reader: R) -> Result<Self, String> {
+        let mut packets = PacketReader::new(reader);
+        let first = packets
+            .read_packet()
+            .map_err(|e| e.to_string())?
+            .ok_or("empty Ogg stream")?;
+        let head = Head::parse(&first.data)?;
+        let channels = match head.channels {
+            1 => Channels::Mono,
+            _ => Channels::Stereo,
+        };
+        let decoder = Decoder::new(RATE, channels).map_err(|e| e.to_string())?;
+        let mut source = Self {
+            packets,
+            decoder,
+            channels: head.channels as ChannelCount,
+            pcm: vec![0.0; MAX_FRAME * head.channels as usize],
+            pos: 0,
+            len: 0,
+            skip: 0,
+        };
+        source.start_stream(&head);
+        Ok(source)
+    }
+
+    fn start_stream(&mut self, head: &Head) {
+        if let Ok(decoder) = Decoder::new(RATE, self.decoder.channels()) {
+            self.decoder = decoder;
+        }
+        let _ = self.decoder.set_gain(head.gain as i32);
+        self.skip = head.pre_skip as usize * self.channels as usize;
+    }
+
+    /// Decodes packets until one yields samples. False at the end of the stream.
+    fn refill(&mut self) -> bool {
+        loop {
+            let packet = match self.packets.read_packet() {
+                Ok(Some(packet)) => packet,
+                Ok(None) | Err(_) => return false,
+            };
+            if packet.data.starts_with(b"OpusHead") {
+                if let Ok(head) = Head::parse(&packet.data) {
+                    self.start_stream(&head);
+                }
+                continue;
+            }
+            if packet.data.starts_with(b"OpusTags") {
+                continue;
+            }
+            // A damaged packet is skipped rather than ending playback
+            let Ok(frames) =
+                self.decoder
+                    .decode_float(&packet.data, &mut self.pcm, DecodeMode::Normal)
+            else {
+                continue;
+            };
+            let len = frames * self.channels as usize;
+            let skipped = self.skip.min(len);
+            self.skip -= skipped;
+            if skipped < len {
+                self.pos = skipped;
+                self.len = len;
+                return true;
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for OpusSource<R> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.pos == self.len && !self.refill() {
+            return None;
+        }
+        let sample = self.pcm[self.pos];
+        self.pos += 1;
+        Some(sample)
+    }
+}
+
+impl<R: Read + Seek> Source for OpusSource<R> {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, OPUS_PRE_SKIP};
+    use std::io::Cursor;
+
+    fn decode(bytes: Vec<u8>) -> (OpusSource<Cursor<Vec<u8>>>, Vec<f32>) {
+        let mut source = OpusSource::new(Cursor::new(bytes)).unwrap();
+        let samples = source.by_ref().collect();
+        (source, samples)
+    }
+
+    #[test]
+    fn decodes_stereo_without_pre_skip() {
+        let (source, samples) = decode(testing::opus(1.0, 2, 1));
+        assert_eq!(source.channels(), 2);
+        assert_eq!(source.sample_rate(), 48_000);
+        assert_eq!(samples.len(), (48_000 - OPUS_PRE_SKIP as usize) * 2);
+        let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!(peak > 0.15 && peak < 0.4, "peak {}", peak);
+    }
+
+    #[test]
+    fn plays_chained_streams() {
+        let (source, samples) = decode(testing::opus(0.5, 1, 3));
+        assert_eq!(source.channels(), 1);
+        assert_eq!(samples.len(), (24_000 - OPUS_PRE_SKIP as usize) * 3);
+    }
+
+    #[test]
+    fn rejects_surround() {
+        let mut head = b"OpusHead".to_vec();
+        head.extend_from_slice(&[1, 6, 0, 0, 0x80, 0xbb, 0, 0, 0, 0, 1]);
+        let err = Head::parse(&head).err().unwrap();
+        assert_eq!(err, "6-channel Opus streams are not supported");
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        assert!(OpusSource::new(Cursor::new(testing::wav(0.1))).is_err());
+    }
+}
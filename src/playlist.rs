@@ -0,0 +1,133 @@
+use stream_download::http::reqwest::{self, Url};
+
+const PLAYLIST_EXTENSIONS: &[&str] = &["m3u", "m3u8", "pls"];
+
+const PLAYLIST_CONTENT_TYPES: &[&str] = &[
+    "audio/x-mpegurl",
+    "audio/mpegurl",
+    "application/x-mpegurl",
+    "application/vnd.apple.mpegurl",
+    "audio/x-scpls",
+    "audio/scpls",
+    "application/pls+xml",
+];
+
+/// True if the url path ends in a playlist extension.
+pub fn is_playlist_url(url: &Url) -> bool {
+    let path = url.path().to_ascii_lowercase();
+    path.rsplit_once('.')
+        .is_some_and(|(_, ext)| PLAYLIST_EXTENSIONS.contains(&ext))
+}
+
+/// True if a `type/subtype` content type names a playlist format.
+pub fn is_playlist_content_type(content_type: &str) -> bool {
+    let content_type = content_type.to_ascii_lowercase();
+    PLAYLIST_CONTENT_TYPES.contains(&content_type.as_str())
+}
+
+/// Extracts stream urls from an M3U, extended M3U or PLS playlist, in order.
+/// Relative entries are resolved against `base`.
+pub fn parse(body: &str, base: &Url) -> Vec<Url> {
+    let body = body.trim_start_matches('\u{feff}').trim_start();
+    let entries = if body.to_ascii_lowercase().starts_with("[playlist]") {
+        parse_pls(body)
+    } else {
+        parse_m3u(body)
+    };
+
+    entries
+        .iter()
+        .filter_map(|entry| base.join(entry).ok())
+        .filter(|url| url.scheme() == "http" || url.scheme() == "https")
+        .collect()
+}
+
+fn parse_m3u(body: &str) -> Vec<String> {
+    body.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+fn parse_pls(body: &str) -> Vec<String> {
+    let mut entries: Vec<(u32, String)> = body
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once('=')?;
+            let index = key.trim().to_ascii_lowercase();
+            let index = index.strip_prefix("file")?.parse().ok()?;
+            Some((index, value.trim().to_string()))
+        })
+        .collect();
+
+    // FileN entries are not guaranteed to appear in order
+    entries.sort_by_key(|(index, _)| *index);
+    entries.into_iter().map(|(_, url)| url).collect()
+}
+
+/// Downloads a playlist and returns the stream urls it contains.
+pub async fn fetch(client: &reqwest::Client, url: &Url) -> Result<Vec<Url>, reqwest::Error> {
+    let response = client.get(url.clone()).send().await?.error_for_status()?;
+    // Redirects may move the playlist, so resolve entries against the final url
+    let base = response.url().clone();
+    let body = response.text().await?;
+    Ok(parse(&body, &base))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("http://radio.example/listen/").unwrap()
+    }
+
+    fn urls(body: &str) -> Vec<String> {
+        parse(body, &base()).iter().map(Url::to_string).collect()
+    }
+
+    #[test]
+    fn pls_entries_are_sorted_by_index() {
+        let body = "[playlist]\n\
+                    File3=http://c.example/three\n\
+                    Title3=Three\n\
+                    File1=http://a.example/one\n\
+                    file2 = http://b.example/two\n\
+                    NumberOfEntries=3\n";
+        assert_eq!(
+            urls(body),
+            [
+                "http://a.example/one",
+                "http://b.example/two",
+                "http://c.example/three"
+            ]
+        );
+    }
+
+    #[test]
+    fn m3u_skips_comments_and_blank_lines() {
+        let body = "\u{feff}#EXTM3U\n\
+                    \n\
+                    #EXTINF:-1,Station\n\
+                    http://a.example/stream\n\
+                    \r\n\
+                    # mirror\n  \
+                    low.mp3  \n\
+                    ftp://b.example/nope\n";
+        assert_eq!(
+            urls(body),
+            [
+                "http://a.example/stream",
+                "http://radio.example/listen/low.mp3"
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_playlists_have_no_entries() {
+        assert!(urls("").is_empty());
+        assert!(urls("#EXTM3U\n\n#EXTINF:-1,Nothing\n").is_empty());
+        assert!(urls("[playlist]\nNumberOfEntries=0\nVersion=2\n").is_empty());
+    }
+}
@@ -1,13 +1,138 @@
-use std::fs::File;
-use std::os::fd::AsRawFd;
 use std::thread;
 use std::time::Duration;
 
-use rodio::{OutputStreamBuilder, Sink, Source};
+use rodio::{Sink, Source};
+use serde::{Deserialize, Serialize};
 
+use crate::audio::Output;
+use crate::clock;
+use crate::storage::PomodoroConfig;
+
+// Custom lengths longer than this are almost certainly a typo
+const MAX_CUSTOM_MINS: u32 = 8 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Mode {
     Focus,
     Break,
+    LongBreak,
+}
+
+/// How a logged session ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// The timer ran out.
+    Completed,
+    Reset,
+    /// loshell was closed mid-session.
+    Quit,
+}
+
+/// One line of the session log.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SessionEntry {
+    pub mode: Mode,
+    /// Unix seconds.
+    pub started_at: u64,
+    pub ended_at: u64,
+    /// The phase length when it ended, without extensions.
+    pub planned_secs: u64,
+    /// Time the timer actually ran, pauses excluded.
+    pub actual_secs: u64,
+    /// Time added with `+`.
+    #[serde(default)]
+    pub extended_secs: u64,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<u64>,
+}
+
+/// Focus and break lengths.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// 25/5
+    #[default]
+    Classic,
+    /// 50/10
+    Extended,
+    /// 52/17
+    Desktime,
+    /// 90/20
+    Ultradian,
+    /// `focus_mins`/`break_mins` from the config.
+    Custom,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [
+        Preset::Classic,
+        Preset::Extended,
+        Preset::Desktime,
+        Preset::Ultradian,
+        Preset::Custom,
+    ];
+
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|&p| p == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    /// Focus and break length in minutes.
+    fn minutes(self, config: &PomodoroConfig) -> (u32, u32) {
+        match self {
+            Preset::Classic => (25, 5),
+            Preset::Extended => (50, 10),
+            Preset::Desktime => (52, 17),
+            Preset::Ultradian => (90, 20),
+            Preset::Custom => (config.focus_mins, config.break_mins),
+        }
+    }
+
+    /// Focus and break length, or why the custom lengths can't be used.
+    pub fn lengths(self, config: &PomodoroConfig) -> Result<(Duration, Duration), String> {
+        let (focus, brk) = self.minutes(config);
+        Ok((
+            check_minutes("focus_mins", focus)?,
+            check_minutes("break_mins", brk)?,
+        ))
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Preset::Classic => "classic",
+            Preset::Extended => "extended",
+            Preset::Desktime => "desktime",
+            Preset::Ultradian => "ultradian",
+            Preset::Custom => "custom",
+        }
+    }
+
+    pub fn label(self, config: &PomodoroConfig) -> String {
+        let (focus, brk) = self.minutes(config);
+        format!("{} {}/{}", self.name(), focus, brk)
+    }
+}
+
+/// The configured long break length, or why it can't be used.
+pub fn long_break_len(config: &PomodoroConfig) -> Result<Duration, String> {
+    check_minutes("long_break_mins", config.long_break_mins)
+}
+
+/// `mins` as a length, or why it can't be used.
+pub fn check_minutes(name: &str, mins: u32) -> Result<Duration, String> {
+    if mins == 0 {
+        return Err(format!("pomodoro {} must be at least 1 minute", name));
+    }
+    if mins > MAX_CUSTOM_MINS {
+        return Err(format!(
+            "pomodoro {} is {} minutes, the most is {}",
+            name, mins, MAX_CUSTOM_MINS
+        ));
+    }
+    Ok(Duration::from_secs(mins as u64 * 60))
 }
 
 pub struct Pomodoro {
@@ -16,67 +141,247 @@ pub struct Pomodoro {
     pub mode: Mode,
     pub remaining: Duration,
 
+    pub preset: Preset,
     pub focus_len: Duration,
     pub break_len: Duration,
+    pub long_break_len: Duration,
+    /// Focus sessions between long breaks, 0 for none.
+    pub long_break_every: u32,
+    /// Focus sessions finished since the last long break.
+    pub completed: u32,
+
+    pub auto_start_breaks: bool,
+    pub auto_start_focus: bool,
+    pub auto_start_countdown_secs: u32,
+    /// Seconds until the next phase starts on its own.
+    pub countdown: Option<u32>,
+
+    // The phase being timed, for the session log
+    started_at: Option<u64>,
+    active_secs: u64,
+    extended_secs: u64,
+    task_id: Option<u64>,
+    // The task tracked in the todo list right now
+    tracked_task: Option<u64>,
+    finished: Vec<SessionEntry>,
 }
 
 impl Pomodoro {
-    pub fn new() -> Self {
-        let focus_len = Duration::from_secs(25 * 60);
-        let break_len = Duration::from_secs(5 * 60);
+    /// Uses the configured preset, or the classic one if it's invalid.
+    pub fn new(config: &PomodoroConfig) -> Self {
+        let (preset, (focus_len, break_len)) = match config.preset.lengths(config) {
+            Ok(lengths) => (config.preset, lengths),
+            Err(_) => (Preset::Classic, Preset::Classic.lengths(config).unwrap()),
+        };
+        let long_break_len = long_break_len(config).unwrap_or(Duration::from_secs(15 * 60));
 
         Self {
             visible: true,
             running: false,
             mode: Mode::Focus,
             remaining: focus_len,
+            preset,
             focus_len,
             break_len,
+            long_break_len,
+            long_break_every: config.long_break_every,
+            completed: 0,
+            auto_start_breaks: config.auto_start_breaks,
+            auto_start_focus: config.auto_start_focus,
+            auto_start_countdown_secs: config.auto_start_countdown_secs,
+            countdown: None,
+            started_at: None,
+            active_secs: 0,
+            extended_secs: 0,
+            task_id: None,
+            tracked_task: None,
+            finished: Vec::new(),
         }
     }
 
+    /// Switches to another preset. Time already spent in the current phase
+    /// counts towards the new length, and minutes added with `+` carry over.
+    pub fn set_preset(&mut self, preset: Preset, config: &PomodoroConfig) -> Result<(), String> {
+        let (focus_len, break_len) = preset.lengths(config)?;
+        let (old_len, new_len) = match self.mode {
+            Mode::Focus => (self.focus_len, focus_len),
+            Mode::Break => (self.break_len, break_len),
+            Mode::LongBreak => (self.long_break_len, self.long_break_len),
+        };
+        let extended = Duration::from_secs(self.extended_secs);
+        let elapsed = (old_len + extended).saturating_sub(self.remaining);
+        self.remaining = (new_len + extended).saturating_sub(elapsed);
+        self.preset = preset;
+        self.focus_len = focus_len;
+        self.break_len = break_len;
+        Ok(())
+    }
+
     pub fn toggle_visible(&mut self) {
         self.visible = !self.visible
     }
 
     pub fn start_pause(&mut self) {
-        self.running = !self.running
+        self.running = !self.running;
+        self.countdown = None;
+        if self.running && self.started_at.is_none() {
+            self.begin_phase();
+        }
+    }
+
+    /// Follows the task tracked in the todo list. A phase is logged against
+    /// the task tracked when it started, or the last one tracked while it
+    /// ran, even if that task is untracked or deleted before it ends.
+    pub fn track_task(&mut self, task_id: Option<u64>) {
+        self.tracked_task = task_id;
+        if task_id.is_some() && self.started_at.is_some() {
+            self.task_id = task_id;
+        }
+    }
+
+    fn begin_phase(&mut self) {
+        self.started_at = Some(clock::now_secs());
+        self.task_id = self.tracked_task;
+    }
+
+    /// Cancels a pending auto-start, leaving the timer paused.
+    pub fn hold(&mut self) {
+        self.countdown = None;
     }
 
     pub fn stop_reset(&mut self) {
+        self.end_session(Outcome::Reset);
         self.running = false;
+        self.countdown = None;
         self.mode = Mode::Focus;
         self.remaining = self.focus_len;
+        self.completed = 0;
     }
 
     pub fn add_five_minutes(&mut self) {
         self.remaining += Duration::from_secs(5 * 60);
+        self.extended_secs += 5 * 60;
+    }
+
+    /// Logs the phase in progress as cut short by quitting.
+    pub fn quit(&mut self) {
+        self.end_session(Outcome::Quit);
+    }
+
+    /// Sessions that ended since the last call, oldest first.
+    pub fn take_finished(&mut self) -> Vec<SessionEntry> {
+        std::mem::take(&mut self.finished)
+    }
+
+    fn phase_len(&self) -> Duration {
+        match self.mode {
+            Mode::Focus => self.focus_len,
+            Mode::Break => self.break_len,
+            Mode::LongBreak => self.long_break_len,
+        }
+    }
+
+    /// Records the current phase if it was ever started.
+    fn end_session(&mut self, outcome: Outcome) {
+        if let Some(started_at) = self.started_at.take() {
+            self.finished.push(SessionEntry {
+                mode: self.mode,
+                started_at,
+                ended_at: clock::now_secs(),
+                planned_secs: self.phase_len().as_secs(),
+                actual_secs: self.active_secs,
+                extended_secs: self.extended_secs,
+                outcome,
+                task_id: self.task_id,
+            });
+        }
+        self.active_secs = 0;
+        self.extended_secs = 0;
+        self.task_id = None;
     }
 
     /// Tick 1 second. Returns true if mode switched (for sound notification).
     pub fn tick_1s(&mut self) -> bool {
+        if let Some(secs) = self.countdown {
+            if secs <= 1 {
+                self.countdown = None;
+                self.running = true;
+                self.begin_phase();
+            } else {
+                self.countdown = Some(secs - 1);
+            }
+            return false;
+        }
         if !self.running {
             return false;
         }
 
         if self.remaining.as_secs() == 0 {
             // switch mode when timer ends
+            self.end_session(Outcome::Completed);
             self.running = false;
             self.mode = match self.mode {
-                Mode::Focus => Mode::Break,
+                Mode::Focus => {
+                    self.completed += 1;
+                    if self.long_break_every > 0 && self.completed >= self.long_break_every {
+                        Mode::LongBreak
+                    } else {
+                        Mode::Break
+                    }
+                }
                 Mode::Break => Mode::Focus,
+                Mode::LongBreak => {
+                    self.completed = 0;
+                    Mode::Focus
+                }
             };
-            self.remaining = match self.mode {
-                Mode::Focus => self.focus_len,
-                Mode::Break => self.break_len,
+            self.remaining = self.phase_len();
+
+            let auto_start = match self.mode {
+                Mode::Focus => self.auto_start_focus,
+                Mode::Break | Mode::LongBreak => self.auto_start_breaks,
             };
+            if auto_start {
+                match self.auto_start_countdown_secs {
+                    0 => {
+                        self.running = true;
+                        self.begin_phase();
+                    }
+                    secs => self.countdown = Some(secs),
+                }
+            }
             return true; // mode switched
         }
 
         self.remaining = self.remaining.saturating_sub(Duration::from_secs(1));
+        self.active_secs += 1;
         false
     }
 
+    /// What's about to start on its own, for the notice line.
+    pub fn countdown_message(&self) -> Option<String> {
+        let secs = self.countdown?;
+        let phase = match self.mode {
+            Mode::Focus => "focus resumes",
+            Mode::Break => "break starts",
+            Mode::LongBreak => "long break starts",
+        };
+        Some(format!(
+            "{} in {}s, space to start now, any key to hold",
+            phase, secs
+        ))
+    }
+
+    /// Progress through the cycle, like `●●○○`. Empty without long breaks.
+    pub fn cycle_dots(&self) -> String {
+        let every = self.long_break_every as usize;
+        let done = (self.completed as usize).min(every);
+        if every > 8 {
+            return format!("{}/{}", done, every);
+        }
+        "●".repeat(done) + &"○".repeat(every - done)
+    }
+
     pub fn mmss(&self) -> (u64, u64) {
         let secs = self.remaining.as_secs();
         (secs / 60, secs % 60)
@@ -86,6 +391,7 @@ impl Pomodoro {
         let (title, body) = match self.mode {
             Mode::Focus => ("Time to focus", "Focus session started. Let's go."),
             Mode::Break => ("Take a break", "Break time. Step away for a bit."),
+            Mode::LongBreak => ("Take a long break", "Cycle done. Get up and recharge."),
         };
         thread::spawn(move || {
             #[cfg(target_os = "macos")]
@@ -104,52 +410,309 @@ impl Pomodoro {
         });
     }
 
-    pub fn play_notification(&self) {
-        let is_focus = matches!(self.mode, Mode::Focus);
-        thread::spawn(move || {
-            let Ok(stream) = OutputStreamBuilder::open_default_stream() else {
-                return;
-            };
-            let sink = Sink::connect_new(stream.mixer());
+    /// Beeps straight into the output, past the radio's duck.
+    pub fn play_notification(&self, output: &Output) {
+        let sink = Sink::connect_new(output.mixer());
 
-            // Different tones for focus vs break
-            // Focus starting: lower, calming tone
-            // Break starting: higher, alert tone
-            let (freq, duration_ms) = if is_focus {
-                (440.0, 300) // A4, calm
-            } else {
-                (880.0, 200) // A5, alert
-            };
+        // Different tones for focus vs break
+        // Focus starting: lower, calming tone
+        // Break starting: higher, alert tone
+        let (freq, duration_ms) = if matches!(self.mode, Mode::Focus) {
+            (440.0, 300) // A4, calm
+        } else {
+            (880.0, 200) // A5, alert
+        };
 
-            // Play 3 beeps - loud enough to hear over music
-            for _ in 0..3 {
-                let beep = rodio::source::SineWave::new(freq)
-                    .take_duration(Duration::from_millis(duration_ms))
-                    .amplify(0.9);
-                sink.append(beep);
+        // Play 3 beeps - loud enough to hear over music
+        for _ in 0..3 {
+            let beep = rodio::source::SineWave::new(freq)
+                .take_duration(Duration::from_millis(duration_ms))
+                .amplify(0.9);
+            sink.append(beep);
 
-                let silence =
-                    rodio::source::Zero::new(1, 44100).take_duration(Duration::from_millis(150));
-                sink.append(silence);
-            }
+            let silence =
+                rodio::source::Zero::new(1, 44100).take_duration(Duration::from_millis(150));
+            sink.append(silence);
+        }
 
-            sink.sleep_until_end();
-            sink.stop();
-            drop(sink);
+        // Plays on without the handle
+        sink.detach();
+    }
+}
 
-            // Suppress rodio's "Dropping OutputStream" message by redirecting stderr
-            unsafe {
-                let null = File::open("/dev/null").ok();
-                let old_stderr = libc::dup(2);
-                if let Some(ref f) = null {
-                    libc::dup2(f.as_raw_fd(), 2);
-                }
-                drop(stream);
-                if old_stderr >= 0 {
-                    libc::dup2(old_stderr, 2);
-                    libc::close(old_stderr);
-                }
-            }
-        });
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the current phase out. True if the timer moved on.
+    fn finish(pomo: &mut Pomodoro) -> bool {
+        pomo.running = true;
+        pomo.remaining = Duration::ZERO;
+        pomo.tick_1s()
+    }
+
+    #[test]
+    fn sessions_keep_the_task_they_were_for() {
+        let config = PomodoroConfig {
+            auto_start_breaks: true,
+            auto_start_countdown_secs: 0,
+            ..Default::default()
+        };
+        let mut pomo = Pomodoro::new(&config);
+        pomo.track_task(Some(1));
+        pomo.start_pause();
+        // Untracking or deleting the task doesn't lose it
+        pomo.track_task(None);
+        pomo.remaining = Duration::ZERO;
+        pomo.tick_1s();
+        // The break starts untracked
+        pomo.quit();
+
+        let sessions = pomo.take_finished();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].task_id, Some(1));
+        assert_eq!(sessions[1].task_id, None);
+    }
+
+    #[test]
+    fn switching_tasks_mid_phase_moves_the_session() {
+        let mut pomo = Pomodoro::new(&PomodoroConfig::default());
+        pomo.start_pause();
+        pomo.track_task(Some(2));
+        pomo.quit();
+        assert_eq!(pomo.take_finished()[0].task_id, Some(2));
+    }
+
+    #[test]
+    fn every_preset_is_labelled_by_name() {
+        let config = PomodoroConfig::default();
+        let labels: Vec<String> = Preset::ALL.iter().map(|p| p.label(&config)).collect();
+        assert_eq!(
+            labels[..4],
+            [
+                "classic 25/5",
+                "extended 50/10",
+                "desktime 52/17",
+                "ultradian 90/20"
+            ]
+        );
+        assert!(labels[4].starts_with("custom "));
+    }
+
+    #[test]
+    fn switching_presets_keeps_elapsed_time() {
+        let config = PomodoroConfig::default();
+        let mut pomo = Pomodoro::new(&config);
+        pomo.set_preset(Preset::Classic, &config).unwrap();
+        pomo.remaining -= Duration::from_secs(10 * 60);
+        pomo.set_preset(Preset::Extended, &config).unwrap();
+        assert_eq!(pomo.remaining, Duration::from_secs(40 * 60));
+    }
+
+    #[test]
+    fn switching_presets_keeps_extensions() {
+        let config = PomodoroConfig::default();
+        let mut pomo = Pomodoro::new(&config);
+        pomo.set_preset(Preset::Classic, &config).unwrap();
+        pomo.remaining -= Duration::from_secs(10 * 60);
+        pomo.add_five_minutes();
+        pomo.set_preset(Preset::Extended, &config).unwrap();
+        assert_eq!(pomo.remaining, Duration::from_secs(45 * 60));
+        pomo.set_preset(Preset::Classic, &config).unwrap();
+        assert_eq!(pomo.remaining, Duration::from_secs(20 * 60));
+    }
+
+    #[test]
+    fn a_long_break_follows_every_nth_focus_session() {
+        let config = PomodoroConfig {
+            long_break_every: 3,
+            long_break_mins: 20,
+            ..Default::default()
+        };
+        let mut pomo = Pomodoro::new(&config);
+        for done in 1..=2 {
+            assert!(finish(&mut pomo));
+            assert_eq!(pomo.mode, Mode::Break);
+            assert_eq!(pomo.completed, done);
+            finish(&mut pomo);
+            assert_eq!(pomo.mode, Mode::Focus);
+        }
+
+        finish(&mut pomo);
+        assert_eq!(pomo.mode, Mode::LongBreak);
+        assert_eq!(pomo.remaining, Duration::from_secs(20 * 60));
+        assert_eq!(pomo.completed, 3);
+
+        finish(&mut pomo);
+        assert_eq!(pomo.mode, Mode::Focus);
+        assert_eq!(pomo.completed, 0);
+        finish(&mut pomo);
+        assert_eq!(pomo.mode, Mode::Break);
+    }
+
+    #[test]
+    fn long_breaks_can_be_turned_off() {
+        let config = PomodoroConfig {
+            long_break_every: 0,
+            ..Default::default()
+        };
+        let mut pomo = Pomodoro::new(&config);
+        for _ in 0..10 {
+            finish(&mut pomo);
+            assert_eq!(pomo.mode, Mode::Break);
+            finish(&mut pomo);
+        }
+    }
+
+    #[test]
+    fn resetting_restarts_the_cycle() {
+        let mut pomo = Pomodoro::new(&PomodoroConfig::default());
+        finish(&mut pomo);
+        finish(&mut pomo);
+        finish(&mut pomo);
+        assert_eq!(pomo.completed, 2);
+
+        pomo.stop_reset();
+        assert_eq!(pomo.completed, 0);
+        assert_eq!(pomo.mode, Mode::Focus);
+    }
+
+    #[test]
+    fn cycle_dots_show_the_progress() {
+        let config = PomodoroConfig {
+            long_break_every: 4,
+            ..Default::default()
+        };
+        let mut pomo = Pomodoro::new(&config);
+        assert_eq!(pomo.cycle_dots(), "○○○○");
+        pomo.completed = 2;
+        assert_eq!(pomo.cycle_dots(), "●●○○");
+        pomo.completed = 4;
+        assert_eq!(pomo.cycle_dots(), "●●●●");
+
+        // Too many dots to read, so they turn into a count
+        pomo.long_break_every = 12;
+        assert_eq!(pomo.cycle_dots(), "4/12");
+        pomo.long_break_every = 0;
+        assert_eq!(pomo.cycle_dots(), "");
+    }
+
+    #[test]
+    fn switching_presets_keeps_the_long_break() {
+        let config = PomodoroConfig {
+            long_break_every: 1,
+            ..Default::default()
+        };
+        let mut pomo = Pomodoro::new(&config);
+        finish(&mut pomo);
+        assert_eq!(pomo.mode, Mode::LongBreak);
+        pomo.remaining -= Duration::from_secs(5 * 60);
+
+        pomo.set_preset(Preset::Ultradian, &config).unwrap();
+        assert_eq!(pomo.long_break_len, Duration::from_secs(15 * 60));
+        assert_eq!(pomo.remaining, Duration::from_secs(10 * 60));
+    }
+
+    fn auto_start(countdown_secs: u32) -> Pomodoro {
+        Pomodoro::new(&PomodoroConfig {
+            auto_start_breaks: true,
+            auto_start_focus: true,
+            auto_start_countdown_secs: countdown_secs,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn the_next_phase_starts_when_the_countdown_runs_out() {
+        let mut pomo = auto_start(3);
+        finish(&mut pomo);
+        assert_eq!(pomo.mode, Mode::Break);
+        assert_eq!(pomo.countdown, Some(3));
+        assert!(!pomo.running);
+
+        assert!(!pomo.tick_1s());
+        assert!(!pomo.tick_1s());
+        assert_eq!(pomo.countdown, Some(1));
+        assert!(!pomo.running);
+
+        assert!(!pomo.tick_1s());
+        assert_eq!(pomo.countdown, None);
+        assert!(pomo.running);
+        // The break is timed from here
+        pomo.quit();
+        let sessions = pomo.take_finished();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].mode, Mode::Break);
+    }
+
+    #[test]
+    fn a_held_countdown_starts_nothing() {
+        let mut pomo = auto_start(3);
+        finish(&mut pomo);
+        pomo.tick_1s();
+        pomo.hold();
+        for _ in 0..10 {
+            assert!(!pomo.tick_1s());
+        }
+        assert!(!pomo.running);
+        assert_eq!(pomo.countdown, None);
+        assert_eq!(pomo.remaining, pomo.break_len);
+
+        // Space starts it as usual
+        pomo.start_pause();
+        assert!(pomo.running);
+    }
+
+    #[test]
+    fn space_skips_the_countdown() {
+        let mut pomo = auto_start(10);
+        finish(&mut pomo);
+        pomo.tick_1s();
+        pomo.start_pause();
+        assert!(pomo.running);
+        assert_eq!(pomo.countdown, None);
+        pomo.tick_1s();
+        assert_eq!(pomo.remaining, pomo.break_len - Duration::from_secs(1));
+
+        pomo.quit();
+        let sessions = pomo.take_finished();
+        assert_eq!(sessions.last().unwrap().mode, Mode::Break);
+        assert_eq!(sessions.last().unwrap().actual_secs, 1);
+    }
+
+    #[test]
+    fn quitting_during_the_countdown_logs_nothing_new() {
+        let mut pomo = auto_start(10);
+        finish(&mut pomo);
+        pomo.take_finished();
+        pomo.tick_1s();
+        pomo.quit();
+        assert!(pomo.take_finished().is_empty());
+    }
+
+    #[test]
+    fn no_focus_time_counts_during_the_countdown() {
+        let mut pomo = auto_start(5);
+        finish(&mut pomo);
+        pomo.hold();
+        finish(&mut pomo);
+        assert_eq!(pomo.mode, Mode::Focus);
+        assert_eq!(pomo.countdown, Some(5));
+
+        for _ in 0..4 {
+            pomo.tick_1s();
+            assert!(!pomo.running);
+            assert_eq!(pomo.remaining, pomo.focus_len);
+        }
+        pomo.tick_1s();
+        assert!(pomo.running);
+        pomo.tick_1s();
+        pomo.tick_1s();
+        assert_eq!(pomo.remaining, pomo.focus_len - Duration::from_secs(2));
+
+        pomo.quit();
+        let sessions = pomo.take_finished();
+        assert_eq!(sessions.last().unwrap().mode, Mode::Focus);
+        assert_eq!(sessions.last().unwrap().actual_secs, 2);
     }
 }
@@ -1,78 +1,646 @@
-use std::fs::File;
-use std::os::fd::AsRawFd;
+use std::collections::BTreeMap;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::sync::{
-    Arc,
-    atomic::{AtomicU8, Ordering},
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicI8, AtomicU8, AtomicU32, Ordering},
 };
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use rodio::{Decoder, OutputStreamBuilder, Sink};
+use rodio::{Decoder, Sink, Source, decoder::DecoderError, source::Zero};
+use serde::{Deserialize, Serialize};
 use stream_download::{
-    Settings, StreamDownload, http::HttpStream, http::reqwest, storage::temp::TempStorageProvider,
+    Settings, StreamDownload,
+    http::{HttpStream, HttpStreamError, reqwest},
+    storage::temp::TempStorageProvider,
 };
 
+use crate::ambient::{Noise, NoiseSource};
+use crate::audio::Output;
+use crate::icy::IcyReader;
+use crate::local;
+use crate::loudness::Normalize;
+use crate::mixer::Mixer;
+use crate::net;
+use crate::opus::OpusSource;
+use crate::playlist;
+use crate::recorder::Recorder;
+use crate::rng::Rng;
+use crate::storage::{
+    DuckConfig, DuckCurve, LayerConfig, LoudnessConfig, NetworkConfig, RadioConfig, StationMode,
+};
+use crate::visualizer::{Scope, Tap};
+
+/// Stream quality. What each level means is up to the station, e.g. 64k AAC
+/// for tethered connections and 256k MP3 at the office.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Station {
-    pub name: &'static str,
-    pub url: &'static str,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub url: String,
+    /// Other streams of the same station, by quality.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variants: BTreeMap<Quality, String>,
+    /// Overrides the global quality preference for this station.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<Quality>,
+    /// Local music folder, played instead of `url` when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shuffle: bool,
+    /// Generated noise, played instead of `url` when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise: Option<Noise>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
-pub const STATIONS: &[Station] = &[
-    Station {
-        name: "Groove Salad",
-        url: "https://ice4.somafm.com/groovesalad-128-mp3",
-    },
-    Station {
-        name: "Drone Zone",
-        url: "https://ice4.somafm.com/dronezone-128-mp3",
-    },
-    Station {
-        name: "Lush",
-        url: "https://ice4.somafm.com/lush-128-mp3",
-    },
-    Station {
-        name: "Deep Space One",
-        url: "https://ice4.somafm.com/deepspaceone-128-mp3",
-    },
-    Station {
-        name: "Vaporwaves",
-        url: "https://ice4.somafm.com/vaporwaves-128-mp3",
-    },
-    Station {
-        name: "Chillits",
-        url: "https://ice4.somafm.com/chillits-128-mp3",
-    },
-    Station {
-        name: "Sonic Universe",
-        url: "https://ice4.somafm.com/sonicuniverse-128-mp3",
-    },
-    Station {
-        name: "Digitalis",
-        url: "https://ice4.somafm.com/digitalis-128-mp3",
+impl Station {
+    fn builtin(name: &str, slug: &str, genre: &str) -> Self {
+        let stream = |variant: &str| format!("https://ice4.somafm.com/{}-{}", slug, variant);
+        Self {
+            name: name.to_string(),
+            url: stream("128-mp3"),
+            variants: BTreeMap::from([
+                (Quality::Low, stream("64-aac")),
+                (Quality::High, stream("256-mp3")),
+            ]),
+            genre: Some(genre.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn generated(noise: Noise) -> Self {
+        Self {
+            name: noise.label().to_string(),
+            noise: Some(noise),
+            genre: Some("noise".to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.path.is_some()
+    }
+
+    pub fn is_stream(&self) -> bool {
+        self.path.is_none() && self.noise.is_none()
+    }
+
+    /// The stream url for the station's own quality preference, else
+    /// `preferred`. Falls back to `url`, then to any variant.
+    pub fn stream_url(&self, preferred: Quality) -> &str {
+        let quality = self.quality.unwrap_or(preferred);
+        if let Some(url) = self.variants.get(&quality) {
+            return url;
+        }
+        if !self.url.is_empty() {
+            return &self.url;
+        }
+        self.variants.values().next().map_or("", String::as_str)
+    }
+
+    /// Checks that the station can actually be played.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err(format!("station with url \"{}\" has no name", self.url));
+        }
+        if self.noise.is_some() {
+            return Ok(());
+        }
+        if let Some(path) = &self.path {
+            if !local::expand_home(path).is_dir() {
+                return Err(format!(
+                    "station \"{}\" points at {}, which is not a folder",
+                    self.name,
+                    path.display()
+                ));
+            }
+            return Ok(());
+        }
+        if self.url.is_empty() && self.variants.is_empty() {
+            return Err(format!("station \"{}\" has no url", self.name));
+        }
+        let urls = std::iter::once(&self.url)
+            .filter(|u| !u.is_empty())
+            .chain(self.variants.values());
+        for url in urls {
+            let url = reqwest::Url::parse(url)
+                .map_err(|e| format!("station \"{}\" has an invalid url: {}", self.name, e))?;
+            if url.scheme() != "http" && url.scheme() != "https" {
+                return Err(format!(
+                    "station \"{}\" must use http or https, got {}",
+                    self.name,
+                    url.scheme()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn builtin_stations() -> Vec<Station> {
+    let mut stations = vec![
+        Station::builtin("Groove Salad", "groovesalad", "ambient/chill"),
+        Station::builtin("Drone Zone", "dronezone", "ambient/space"),
+        Station::builtin("Lush", "lush", "electronica/vocals"),
+        Station::builtin("Deep Space One", "deepspaceone", "ambient/space"),
+        Station::builtin("Vaporwaves", "vaporwaves", "vaporwave"),
+        Station::builtin("Chillits", "chillits", "chill"),
+        Station::builtin("Sonic Universe", "sonicuniverse", "jazz"),
+        Station::builtin("Digitalis", "digitalis", "electronica"),
+    ];
+    // Offline stations that work without any network
+    stations.extend(Noise::ALL.into_iter().map(Station::generated));
+    stations
+}
+
+/// Builds the station list from the built-ins and the user's configured
+/// stations. Invalid entries are skipped and returned as warnings.
+pub fn load_stations(
+    custom: &[serde_json::Value],
+    mode: StationMode,
+) -> (Vec<Station>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let mut stations = match mode {
+        StationMode::Extend => builtin_stations(),
+        StationMode::Replace => Vec::new(),
+    };
+
+    for (i, entry) in custom.iter().enumerate() {
+        let station = match Station::deserialize(entry) {
+            Ok(station) => station,
+            Err(e) => {
+                // Name the entry if it has one, else its place in the list
+                let label = match entry.get("name").and_then(|name| name.as_str()) {
+                    Some(name) => format!("\"{}\"", name),
+                    None => format!("#{}", i + 1),
+                };
+                warnings.push(format!("station {} is invalid: {}", label, e));
+                continue;
+            }
+        };
+        match station.validate() {
+            Ok(()) => {
+                // A custom station with a built-in name overrides it
+                match stations.iter_mut().find(|s| s.name == station.name) {
+                    Some(existing) => *existing = station,
+                    None => stations.push(station),
+                }
+            }
+            Err(e) => warnings.push(e),
+        }
+    }
+
+    if stations.is_empty() {
+        warnings.push("no valid stations configured, using built-in list".to_string());
+        stations = builtin_stations();
+    }
+
+    (stations, warnings)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    NoDevice,
+    Dns,
+    Network,
+    Timeout,
+    Tls,
+    Http(u16),
+    InvalidUrl,
+    Playlist,
+    UnsupportedFormat,
+    Decode,
+    Storage,
+    NoTracks,
+    Proxy,
+    Config,
+}
+
+#[derive(Clone)]
+pub struct RadioError {
+    pub kind: ErrorKind,
+    pub message: String,
+    transient: bool,
+}
+
+impl RadioError {
+    fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        let transient = match kind {
+            ErrorKind::Dns | ErrorKind::Network | ErrorKind::Timeout | ErrorKind::Proxy => true,
+            ErrorKind::Http(status) => transient_status(status),
+            _ => false,
+        };
+        Self {
+            kind,
+            message: message.into(),
+            transient,
+        }
+    }
+
+    /// Retrying is decided from what reqwest reports; the message only picks
+    /// the label shown for connection failures.
+    fn from_reqwest(err: &reqwest::Error) -> Self {
+        let message = error_chain(err);
+        let (kind, transient) = if let Some(status) = err.status() {
+            let status = status.as_u16();
+            (ErrorKind::Http(status), transient_status(status))
+        } else if err.is_timeout() {
+            (ErrorKind::Timeout, true)
+        } else if is_tls(err) {
+            // A certificate the system doesn't trust won't start being trusted
+            (ErrorKind::Tls, false)
+        } else if err.is_builder() {
+            (ErrorKind::InvalidUrl, false)
+        } else {
+            let transient =
+                err.is_connect() || err.is_request() || err.is_body() || err.is_decode();
+            (connection_kind(&message), transient)
+        };
+        Self {
+            kind,
+            message,
+            transient,
+        }
+    }
+
+    /// Short reason for the station line.
+    pub fn reason(&self) -> String {
+        match self.kind {
+            ErrorKind::NoDevice => "no audio device".to_string(),
+            ErrorKind::Dns => "DNS lookup failed".to_string(),
+            ErrorKind::Network => "network error".to_string(),
+            ErrorKind::Timeout => "timed out".to_string(),
+            ErrorKind::Tls => "TLS error".to_string(),
+            ErrorKind::Http(status) => format!("HTTP {}", status),
+            ErrorKind::InvalidUrl => "invalid url".to_string(),
+            ErrorKind::Playlist => "bad playlist".to_string(),
+            ErrorKind::UnsupportedFormat => "unsupported format".to_string(),
+            ErrorKind::Decode => "decode error".to_string(),
+            ErrorKind::Proxy => "proxy error".to_string(),
+            ErrorKind::Config => "bad network settings".to_string(),
+            ErrorKind::Storage => "buffer error".to_string(),
+            ErrorKind::NoTracks => "no tracks found".to_string(),
+        }
+    }
+
+    /// Whether retrying could help. A 404 or an unknown codec won't fix itself.
+    fn is_transient(&self) -> bool {
+        self.transient
+    }
+}
+
+fn transient_status(status: u16) -> bool {
+    status >= 500 || status == 408 || status == 429
+}
+
+fn is_tls(err: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(e) = source {
+        if e.is::<native_tls::Error>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+// reqwest doesn't say why a connection failed, only that it did
+fn connection_kind(message: &str) -> ErrorKind {
+    let lower = message.to_lowercase();
+    if lower.contains("proxy") {
+        ErrorKind::Proxy
+    } else if ["dns", "resolve", "lookup", "name or service"]
+        .iter()
+        .any(|s| lower.contains(s))
+    {
+        ErrorKind::Dns
+    } else {
+        ErrorKind::Network
+    }
+}
+
+// reqwest keeps the useful part (e.g. "certificate verify failed") in the source chain
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
+
+#[derive(Clone)]
+pub enum RadioState {
+    Stopped,
+    Loading,
+    Playing,
+    Reconnecting {
+        attempt: u32,
+        last_error: RadioError,
     },
-];
+    Error(RadioError),
+}
 
-// Radio states
-const STATE_STOPPED: u8 = 0;
-const STATE_LOADING: u8 = 1;
-const STATE_PLAYING: u8 = 2;
-const STATE_ERROR: u8 = 3;
+// Playing this long resets the retry counter
+const STABLE_AFTER: Duration = Duration::from_secs(30);
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
 
-pub struct Radio {
-    pub current_station: usize,
-    state: Arc<AtomicU8>,
+/// How a dropped stream is retried.
+#[derive(Clone, Copy)]
+struct Retry {
+    max_retries: u32,
+    stable_after: Duration,
+    base: Duration,
+    max: Duration,
+}
+
+impl Retry {
+    fn new(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            stable_after: STABLE_AFTER,
+            base: BACKOFF_BASE,
+            max: BACKOFF_MAX,
+        }
+    }
+
+    /// Exponential backoff with +/-25% jitter so many clients don't retry in lockstep.
+    fn backoff(&self, attempt: u32, rng: &mut Rng) -> Duration {
+        let exp = self
+            .base
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16));
+        let jitter = 1.0 + rng.next_signed() as f64 / 4.0;
+        exp.min(self.max).mul_f64(jitter)
+    }
+}
+// The sleep timer fades out over this long before stopping
+const SLEEP_FADE: Duration = Duration::from_secs(10);
+// How often the mix picks up duck and sleep fade changes
+const MIX_UPDATE: Duration = Duration::from_millis(10);
+// Per-station gain offsets stay within ± this many dB
+const MAX_STATION_GAIN: f32 = 12.0;
+
+/// Gain envelope for a crossfade between two stations.
+#[derive(Clone, Copy)]
+enum Fade {
+    Full,
+    Silent,
+    In { start: Instant, len: Duration },
+    Out { start: Instant, len: Duration },
+}
+
+impl Fade {
+    // Equal-power curve so the overall loudness doesn't dip mid-fade
+    fn gain(&self) -> f32 {
+        let progress = |start: &Instant, len: &Duration| {
+            if len.is_zero() {
+                1.0
+            } else {
+                (start.elapsed().as_secs_f32() / len.as_secs_f32()).min(1.0)
+            }
+        };
+        match self {
+            Fade::Full => 1.0,
+            Fade::Silent => 0.0,
+            Fade::In { start, len } => (progress(start, len) * FRAC_PI_2).sin(),
+            Fade::Out { start, len } => (progress(start, len) * FRAC_PI_2).cos(),
+        }
+    }
+
+    fn finished(&self) -> bool {
+        match self {
+            Fade::Out { start, len } => start.elapsed() >= *len,
+            _ => false,
+        }
+    }
+}
+
+/// A temporary drop in volume, on top of the user's volume so it always
+/// comes back to where it was.
+#[derive(Clone)]
+struct Duck {
+    start: Instant,
+    config: DuckConfig,
+}
+
+impl Duck {
+    // The config is validated on load, so this only guards against overflow
+    fn len(&self) -> Duration {
+        Duration::try_from_secs_f32(self.config.fade_secs * 2.0 + self.config.hold_secs)
+            .unwrap_or(Duration::ZERO)
+    }
+
+    fn gain(&self) -> f32 {
+        let depth = self.config.depth;
+        let fade = self.config.fade_secs;
+        let hold = self.config.hold_secs;
+        let t = self.start.elapsed().as_secs_f32();
+
+        // How far down we are, 0.0 (full volume) to 1.0 (fully ducked)
+        let amount = if t < fade {
+            t / fade
+        } else if t < fade + hold {
+            1.0
+        } else if t < fade * 2.0 + hold {
+            1.0 - (t - fade - hold) / fade
+        } else {
+            0.0
+        };
+
+        match self.config.curve {
+            DuckCurve::Linear => 1.0 - (1.0 - depth) * amount,
+            DuckCurve::Smooth => {
+                let eased = (1.0 - (amount * PI).cos()) / 2.0;
+                1.0 - (1.0 - depth) * eased
+            }
+            // Silence is infinitely far away in decibels, so stop just short of it
+            DuckCurve::Exponential => depth.max(0.001).powf(amount),
+        }
+    }
+}
+
+/// Handles shared between `Radio` and a playback thread.
+#[derive(Clone)]
+struct Shared {
+    state: Arc<Mutex<RadioState>>,
     stop_flag: Arc<AtomicU8>,
-    volume: Arc<AtomicU8>, // 0-100
+    volume: Arc<AtomicU8>, // 0-100, the same for every thread
+    muted: Arc<AtomicBool>,
+    normalize: Arc<AtomicBool>,
+    target_db: f32,
+    gain_db: Arc<AtomicU32>, // f32 bits
+    fade: Arc<Mutex<Fade>>,
+    title: Arc<Mutex<Option<String>>>,
+    skip: Arc<AtomicI8>, // pending track skip for local folders
+    recorder: Arc<Mutex<Recorder>>,
+}
+
+impl Shared {
+    fn stopped(&self) -> bool {
+        self.stop_flag.load(Ordering::SeqCst) == 1
+    }
+
+    fn set_state(&self, state: RadioState) {
+        *self.state.lock().unwrap() = state;
+    }
+
+    fn set_title(&self, title: Option<String>) {
+        *self.title.lock().unwrap() = title;
+    }
+
+    fn set_fade(&self, fade: Fade) {
+        *self.fade.lock().unwrap() = fade;
+    }
+
+    /// Wraps a decoded source in loudness processing.
+    fn process<S: Source>(&self, source: S) -> Normalize<S> {
+        Normalize::new(
+            source,
+            self.normalize.clone(),
+            self.gain_db.clone(),
+            self.target_db,
+        )
+    }
+
+    fn apply_volume(&self, sink: &Sink) {
+        let vol = if self.muted.load(Ordering::SeqCst) {
+            0.0
+        } else {
+            self.volume.load(Ordering::SeqCst) as f32 / 100.0
+        };
+        sink.set_volume(vol * self.fade.lock().unwrap().gain());
+    }
+}
+
+/// A playback thread for one station.
+struct Worker {
+    station: usize,
+    shared: Shared,
     handle: Option<JoinHandle<()>>,
 }
 
+impl Worker {
+    fn state(&self) -> RadioState {
+        self.shared.state.lock().unwrap().clone()
+    }
+
+    fn fade(&self) -> Fade {
+        *self.shared.fade.lock().unwrap()
+    }
+
+    /// Stops playback and waits for the thread to finish.
+    fn stop(mut self) {
+        self.shared.stop_flag.store(1, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        self.shared.recorder.lock().unwrap().stop();
+    }
+
+    /// Stops playback without waiting, for threads that may still be stuck
+    /// connecting. The thread exits on its own.
+    fn retire(self) {
+        self.shared.stop_flag.store(1, Ordering::SeqCst);
+        self.shared.recorder.lock().unwrap().stop();
+    }
+}
+
+/// When the sleep timer stops the radio.
+#[derive(Clone, Copy)]
+pub enum Sleep {
+    At(Instant),
+    /// When the current pomodoro break ends.
+    AfterBreak,
+}
+
+/// A station that failed to start while another one kept playing.
+pub struct SwitchFailure {
+    pub station: String,
+    pub error: RadioError,
+}
+
+pub struct Radio {
+    pub current_station: usize,
+    /// Ambient layers, playing into the same mix as the station.
+    pub mixer: Mixer,
+    stations: Vec<Station>,
+    volume: Arc<AtomicU8>,
+    muted: Arc<AtomicBool>,
+    duck: Arc<Mutex<Option<Duck>>>,
+    sleep_fade: Arc<Mutex<Fade>>,
+    scope: Arc<Mutex<Scope>>,
+    normalize: Arc<AtomicBool>,
+    config: RadioConfig,
+    network: NetworkConfig,
+    loudness: LoudnessConfig,
+    // The station and layers mix into this, or the reason there is no audio device
+    music: Result<rodio::mixer::Mixer, String>,
+    retry: Retry,
+    // The audible station
+    current: Option<Worker>,
+    // A station buffering in the background, faded in once it plays
+    pending: Option<Worker>,
+    // The previous station, fading out
+    fading: Option<Worker>,
+    sleep: Option<Sleep>,
+}
+
 impl Radio {
-    pub fn new() -> Self {
+    pub fn new(
+        stations: Vec<Station>,
+        config: RadioConfig,
+        network: NetworkConfig,
+        loudness: LoudnessConfig,
+        layers: &[LayerConfig],
+        output: &Result<Output, String>,
+    ) -> Self {
+        let duck = Arc::new(Mutex::new(None));
+        let sleep_fade = Arc::new(Mutex::new(Fade::Full));
+        let scope = Arc::new(Mutex::new(Scope::default()));
+        let music = match output {
+            Ok(output) => Ok(music_mix(output, &duck, &sleep_fade, &scope)),
+            Err(e) => Err(e.clone()),
+        };
+        let max_retries = config.max_retries;
         Self {
             current_station: 0,
-            state: Arc::new(AtomicU8::new(STATE_STOPPED)),
-            stop_flag: Arc::new(AtomicU8::new(0)),
+            mixer: Mixer::new(layers, music.as_ref().ok()),
+            stations,
             volume: Arc::new(AtomicU8::new(100)),
-            handle: None,
+            muted: Arc::new(AtomicBool::new(false)),
+            duck,
+            sleep_fade,
+            scope,
+            normalize: Arc::new(AtomicBool::new(loudness.normalize)),
+            config,
+            network,
+            retry: Retry::new(max_retries),
+            loudness,
+            music,
+            current: None,
+            pending: None,
+            fading: None,
+            sleep: None,
         }
     }
 
@@ -80,181 +648,739 @@ impl Radio {
         self.volume.store(vol.min(100), Ordering::SeqCst);
     }
 
-    pub fn volume_handle(&self) -> Arc<AtomicU8> {
-        self.volume.clone()
-    }
-
     pub fn volume(&self) -> u8 {
         self.volume.load(Ordering::SeqCst)
     }
 
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::SeqCst)
+    }
+
+    pub fn toggle_mute(&self) {
+        self.muted.fetch_xor(true, Ordering::SeqCst);
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        self.muted.store(muted, Ordering::SeqCst);
+    }
+
+    /// Fades the radio down, holds, then fades it back up. Volume changes
+    /// made meanwhile still apply. Does nothing if already ducking.
+    pub fn duck(&self, config: &DuckConfig) {
+        let mut duck = self.duck.lock().unwrap();
+        if duck.as_ref().is_some_and(|d| d.start.elapsed() < d.len()) {
+            return;
+        }
+        *duck = Some(Duck {
+            start: Instant::now(),
+            config: config.clone(),
+        });
+    }
+
+    pub fn is_normalizing(&self) -> bool {
+        self.normalize.load(Ordering::SeqCst)
+    }
+
+    pub fn toggle_normalize(&mut self) {
+        self.loudness.normalize = !self.loudness.normalize;
+        self.normalize
+            .store(self.loudness.normalize, Ordering::SeqCst);
+    }
+
+    /// Gain offset of the selected station in dB.
+    pub fn station_gain(&self) -> f32 {
+        self.loudness
+            .station_gain_db
+            .get(&self.station().name)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Changes the selected station's gain offset, taking effect right away.
+    pub fn adjust_station_gain(&mut self, delta_db: f32) {
+        let gain = (self.station_gain() + delta_db).clamp(-MAX_STATION_GAIN, MAX_STATION_GAIN);
+        let name = self.station().name.clone();
+        if gain == 0.0 {
+            self.loudness.station_gain_db.remove(&name);
+        } else {
+            self.loudness.station_gain_db.insert(name, gain);
+        }
+        for worker in [&self.current, &self.pending].into_iter().flatten() {
+            if worker.station == self.current_station {
+                worker
+                    .shared
+                    .gain_db
+                    .store(gain.to_bits(), Ordering::SeqCst);
+            }
+        }
+    }
+
+    pub fn loudness(&self) -> &LoudnessConfig {
+        &self.loudness
+    }
+
     pub fn station(&self) -> &Station {
-        &STATIONS[self.current_station]
+        &self.stations[self.current_station]
+    }
+
+    /// Station name and track title being heard, both from the same worker,
+    /// so while switching it's still the old station. The title is the ICY
+    /// stream title, or the file name when playing a local folder.
+    pub fn now_playing(&self) -> Option<(String, String)> {
+        let worker = self.current.as_ref()?;
+        if !matches!(worker.state(), RadioState::Playing) {
+            return None;
+        }
+        let title = worker.shared.title.lock().unwrap().clone()?;
+        Some((self.stations[worker.station].name.clone(), title))
+    }
+
+    /// The latest audio of the mix being heard, for the visualizer.
+    pub fn scope(&self) -> Option<Scope> {
+        self.music.as_ref().ok()?;
+        Some(self.scope.lock().unwrap().clone())
     }
 
-    pub fn is_loading(&self) -> bool {
-        self.state.load(Ordering::SeqCst) == STATE_LOADING
+    /// State of the selected station. While switching, that's the station
+    /// being tuned in, not the one still playing.
+    pub fn state(&self) -> RadioState {
+        match self.pending.as_ref().or(self.current.as_ref()) {
+            Some(worker) => worker.state(),
+            None => RadioState::Stopped,
+        }
     }
 
     pub fn is_playing(&self) -> bool {
-        self.state.load(Ordering::SeqCst) == STATE_PLAYING
+        matches!(self.state(), RadioState::Playing)
+    }
+
+    /// True if the station or any ambient layer can be heard.
+    pub fn is_audible(&self) -> bool {
+        self.is_playing() || self.mixer.is_audible()
+    }
+
+    /// The error that stopped playback, if any.
+    pub fn error(&self) -> Option<RadioError> {
+        match self.state() {
+            RadioState::Error(e) => Some(e),
+            _ => None,
+        }
     }
 
-    pub fn is_error(&self) -> bool {
-        self.state.load(Ordering::SeqCst) == STATE_ERROR
+    /// True while the radio is playing or trying to.
+    pub fn is_active(&self) -> bool {
+        !matches!(self.state(), RadioState::Stopped | RadioState::Error(_))
     }
 
     pub fn toggle(&mut self) {
-        let state = self.state.load(Ordering::SeqCst);
-        if state == STATE_STOPPED || state == STATE_ERROR {
-            self.play();
-        } else {
+        if self.is_active() {
             self.stop();
+        } else {
+            self.play();
         }
     }
 
     pub fn play(&mut self) {
-        let current_state = self.state.load(Ordering::SeqCst);
-        if current_state == STATE_LOADING || current_state == STATE_PLAYING {
+        if self.is_active() {
             return;
         }
+        self.stop();
+        self.current = Some(self.spawn(self.current_station, Fade::Full));
+    }
+
+    pub fn stop(&mut self) {
+        // Only a playing station is waited for. The others may be stuck
+        // connecting, which would hold up the UI.
+        for worker in [self.pending.take(), self.fading.take()]
+            .into_iter()
+            .flatten()
+        {
+            worker.retire();
+        }
+        if let Some(current) = self.current.take() {
+            match current.state() {
+                RadioState::Playing => current.stop(),
+                _ => current.retire(),
+            }
+        }
+    }
 
-        self.stop_flag.store(0, Ordering::SeqCst);
-        self.state.store(STATE_LOADING, Ordering::SeqCst);
+    pub fn has_station(&self, name: &str) -> bool {
+        self.stations.iter().any(|s| s.name == name)
+    }
 
-        let url = STATIONS[self.current_station].url.to_string();
-        let stop_flag = self.stop_flag.clone();
-        let state = self.state.clone();
-        let volume = self.volume.clone();
+    pub fn add_station(&mut self, station: Station) {
+        self.stations.push(station);
+    }
 
-        let handle = thread::spawn(move || {
-            // Create audio output
-            let audio_stream = match OutputStreamBuilder::open_default_stream() {
-                Ok(s) => s,
-                Err(_) => {
-                    state.store(STATE_ERROR, Ordering::SeqCst);
-                    return;
-                }
-            };
+    /// Selects a station by name without starting it. Unknown names are ignored.
+    pub fn select(&mut self, name: &str) {
+        if let Some(index) = self.stations.iter().position(|s| s.name == name) {
+            self.current_station = index;
+        }
+    }
 
-            let sink = Sink::connect_new(audio_stream.mixer());
+    pub fn next_station(&mut self) {
+        self.switch_to((self.current_station + 1) % self.stations.len());
+    }
 
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            rt.block_on(async {
-                // Check if stopped while setting up
-                if stop_flag.load(Ordering::SeqCst) == 1 {
-                    return;
+    pub fn prev_station(&mut self) {
+        let index = if self.current_station == 0 {
+            self.stations.len() - 1
+        } else {
+            self.current_station - 1
+        };
+        self.switch_to(index);
+    }
+
+    /// Tunes in to another station. While a station is playing, the new one
+    /// buffers in the background and `tick` crossfades to it once it's ready.
+    fn switch_to(&mut self, index: usize) {
+        self.current_station = index;
+        if let Some(pending) = self.pending.take() {
+            pending.retire();
+        }
+
+        let current = self.current.as_ref().map(|w| (w.station, w.state()));
+        match current {
+            // Back to the station that is still playing
+            Some((station, RadioState::Playing)) if station == index => {}
+            Some((_, RadioState::Playing)) => {
+                self.pending = Some(self.spawn(index, Fade::Silent));
+            }
+            _ => {
+                let was_active = self.is_active();
+                self.stop();
+                if was_active {
+                    self.play();
                 }
+            }
+        }
+    }
 
-                let client = reqwest::Client::builder()
-                    .redirect(reqwest::redirect::Policy::limited(10))
-                    .build()
-                    .unwrap();
+    /// Moves a station switch along: starts the crossfade once the new
+    /// station plays and cleans up the old one when the fade is done. If the
+    /// new station fails, the old one keeps playing and the failure is
+    /// returned.
+    pub fn tick(&mut self) -> Option<SwitchFailure> {
+        self.tick_sleep();
 
-                let http_stream = match HttpStream::new(client, url.parse().unwrap()).await {
-                    Ok(s) => s,
-                    Err(_) => {
-                        state.store(STATE_ERROR, Ordering::SeqCst);
-                        return;
-                    }
-                };
+        if self.fading.as_ref().is_some_and(|w| w.fade().finished())
+            && let Some(fading) = self.fading.take()
+        {
+            fading.retire();
+        }
 
-                if stop_flag.load(Ordering::SeqCst) == 1 {
-                    return;
+        let state = self.pending.as_ref()?.state();
+        match state {
+            RadioState::Playing => {
+                let pending = self.pending.take()?;
+                let len = Duration::try_from_secs_f32(self.config.crossfade_secs)
+                    .unwrap_or(Duration::ZERO);
+                let start = Instant::now();
+                pending.shared.set_fade(Fade::In { start, len });
+                if let Some(previous) = self.fading.take() {
+                    previous.retire();
+                }
+                if let Some(current) = self.current.replace(pending) {
+                    current.shared.set_fade(Fade::Out { start, len });
+                    current.shared.recorder.lock().unwrap().stop();
+                    self.fading = Some(current);
+                }
+                None
+            }
+            RadioState::Error(error)
+            | RadioState::Reconnecting {
+                last_error: error, ..
+            } => {
+                let pending = self.pending.take()?;
+                let station = self.stations[pending.station].name.clone();
+                pending.retire();
+                if let Some(current) = &self.current {
+                    self.current_station = current.station;
                 }
+                Some(SwitchFailure { station, error })
+            }
+            _ => None,
+        }
+    }
 
-                let reader = match StreamDownload::from_stream(
-                    http_stream,
-                    TempStorageProvider::new(),
-                    Settings::default(),
-                )
-                .await
-                {
-                    Ok(r) => r,
-                    Err(_) => {
-                        state.store(STATE_ERROR, Ordering::SeqCst);
-                        return;
-                    }
-                };
+    pub fn sleep(&self) -> Option<Sleep> {
+        self.sleep
+    }
+
+    /// Cycles the sleep timer: off, 15, 30, 60 minutes, after this break.
+    pub fn cycle_sleep(&mut self) {
+        let minutes = |m: u64| Sleep::At(Instant::now() + Duration::from_secs(m * 60));
+        self.sleep = match self.sleep {
+            None => Some(minutes(15)),
+            Some(Sleep::At(end)) => {
+                // Step up from whatever is left, so a running timer isn't shortened
+                let left = end.saturating_duration_since(Instant::now()).as_secs();
+                match left {
+                    0..=899 => Some(minutes(30)),
+                    900..=1799 => Some(minutes(60)),
+                    _ => Some(Sleep::AfterBreak),
+                }
+            }
+            Some(Sleep::AfterBreak) => None,
+        };
+
+        // Cancelled or pushed back mid fade-out
+        let mut sleep_fade = self.sleep_fade.lock().unwrap();
+        if matches!(*sleep_fade, Fade::Out { .. }) {
+            *sleep_fade = Fade::Full;
+        }
+    }
+
+    /// Called when a pomodoro break is over, for `Sleep::AfterBreak`.
+    pub fn break_ended(&mut self) {
+        if matches!(self.sleep, Some(Sleep::AfterBreak)) {
+            self.sleep = Some(Sleep::At(Instant::now() + SLEEP_FADE));
+        }
+    }
 
-                if stop_flag.load(Ordering::SeqCst) == 1 {
+    /// Fades the whole mix out, layers included, then stops everything.
+    fn tick_sleep(&mut self) {
+        let Some(Sleep::At(end)) = self.sleep else {
+            // Everything stopped a tick ago, so it's safe to bring the mix back up
+            let mut sleep_fade = self.sleep_fade.lock().unwrap();
+            if matches!(*sleep_fade, Fade::Silent) {
+                *sleep_fade = Fade::Full;
+            }
+            return;
+        };
+        let now = Instant::now();
+        if now >= end {
+            self.sleep = None;
+            *self.sleep_fade.lock().unwrap() = Fade::Silent;
+            self.stop();
+            self.mixer.stop_all();
+        } else if end - now <= SLEEP_FADE {
+            let start = end.checked_sub(SLEEP_FADE).unwrap_or(now);
+            *self.sleep_fade.lock().unwrap() = Fade::Out {
+                start,
+                len: SLEEP_FADE,
+            };
+        }
+    }
+
+    fn spawn(&self, index: usize, fade: Fade) -> Worker {
+        let shared = Shared {
+            state: Arc::new(Mutex::new(RadioState::Loading)),
+            stop_flag: Arc::new(AtomicU8::new(0)),
+            volume: self.volume.clone(),
+            muted: self.muted.clone(),
+            normalize: self.normalize.clone(),
+            target_db: self.loudness.target_db,
+            gain_db: Arc::new(AtomicU32::new(
+                self.loudness
+                    .station_gain_db
+                    .get(&self.stations[index].name)
+                    .copied()
+                    .unwrap_or(0.0)
+                    .to_bits(),
+            )),
+            fade: Arc::new(Mutex::new(fade)),
+            title: Arc::new(Mutex::new(None)),
+            skip: Arc::new(AtomicI8::new(0)),
+            recorder: Arc::new(Mutex::new(Recorder::new())),
+        };
+
+        let mut station = self.stations[index].clone();
+        station.url = station.stream_url(self.config.quality).to_string();
+        let retry = self.retry;
+        let network = self.network.clone();
+        let music = self.music.clone();
+        let worker_shared = shared.clone();
+
+        let handle = thread::spawn(move || {
+            let shared = worker_shared;
+
+            let music = match music {
+                Ok(music) => music,
+                Err(e) => {
+                    let err = RadioError::new(ErrorKind::NoDevice, e);
+                    shared.set_state(RadioState::Error(err));
                     return;
                 }
+            };
 
-                let source = match Decoder::new(reader) {
-                    Ok(s) => s,
-                    Err(_) => {
-                        state.store(STATE_ERROR, Ordering::SeqCst);
-                        return;
-                    }
-                };
+            let sink = Sink::connect_new(&music);
 
-                sink.append(source);
-                sink.set_volume(volume.load(Ordering::SeqCst) as f32 / 100.0);
-                sink.play();
+            if let Some(noise) = station.noise {
+                play_noise(&sink, noise, &shared);
+            } else if let Some(dir) = &station.path {
+                play_folder(&sink, &local::expand_home(dir), station.shuffle, &shared);
+            } else {
+                play_stream(&sink, &station, retry, &network, &shared);
+            }
+
+            sink.stop();
+        });
+
+        Worker {
+            station: index,
+            shared,
+            handle: Some(handle),
+        }
+    }
+
+    /// Starts or stops recording the current stream. Only internet streams
+    /// can be recorded.
+    pub fn toggle_recording(&self) {
+        let Some(worker) = &self.current else {
+            return;
+        };
+        let mut recorder = worker.shared.recorder.lock().unwrap();
+        if recorder.is_recording() {
+            recorder.stop();
+        } else if self.stations[worker.station].is_stream() {
+            recorder.start();
+        }
+    }
+
+    /// Bytes written and seconds elapsed, while recording.
+    pub fn recording(&self) -> Option<(u64, u64)> {
+        let recorder = self.current.as_ref()?.shared.recorder.lock().unwrap();
+        recorder
+            .is_recording()
+            .then(|| (recorder.bytes(), recorder.elapsed_secs()))
+    }
+
+    /// Why recording stopped on its own, if it did since the last call.
+    pub fn take_recording_error(&self) -> Option<String> {
+        let mut recorder = self.current.as_ref()?.shared.recorder.lock().unwrap();
+        recorder.take_error()
+    }
+
+    /// Skips to the next track of a local folder station.
+    pub fn next_track(&self) {
+        if let Some(worker) = &self.current {
+            worker.shared.skip.store(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Goes back to the previous track of a local folder station.
+    pub fn prev_track(&self) {
+        if let Some(worker) = &self.current {
+            worker.shared.skip.store(-1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Sets up the mix the station and the layers play into. The duck and the
+/// sleep fade apply to all of it, and the visualizer sees what is heard.
+fn music_mix(
+    output: &Output,
+    duck: &Arc<Mutex<Option<Duck>>>,
+    sleep_fade: &Arc<Mutex<Fade>>,
+    scope: &Arc<Mutex<Scope>>,
+) -> rodio::mixer::Mixer {
+    let (music, source) = rodio::mixer::mixer(output.channels(), output.sample_rate());
+    // An empty mix ends, so keep silence in it
+    music.add(Zero::new(output.channels(), output.sample_rate()));
+
+    let duck = duck.clone();
+    let sleep_fade = sleep_fade.clone();
+    let source = source.amplify(1.0).periodic_access(MIX_UPDATE, move |mix| {
+        let duck = duck.lock().unwrap().as_ref().map_or(1.0, Duck::gain);
+        mix.set_factor(duck * sleep_fade.lock().unwrap().gain());
+    });
+    output.mixer().add(Tap::new(source, scope.clone()));
+    music
+}
+
+fn play_stream(
+    sink: &Sink,
+    station: &Station,
+    retry: Retry,
+    network: &NetworkConfig,
+    shared: &Shared,
+) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    rt.block_on(async {
+        // Check if stopped while setting up
+        if shared.stopped() {
+            return;
+        }
+
+        // Ask for inline track titles
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            "Icy-MetaData",
+            reqwest::header::HeaderValue::from_static("1"),
+        );
+
+        // A stalled connection errors out after the read timeout and counts as dropped
+        let client = match net::client(network, headers) {
+            Ok(client) => client,
+            Err(e) => {
+                shared.set_state(RadioState::Error(RadioError::new(ErrorKind::Config, e)));
+                return;
+            }
+        };
+
+        let mut rng = Rng::from_time();
+        let mut failures: u32 = 0;
+        loop {
+            let err = match open_source(&client, station, shared).await {
+                Ok(None) => break,
+                Ok(Some(source)) => {
+                    sink.append(shared.process(source));
+                    shared.apply_volume(sink);
+                    sink.play();
+
+                    // Now playing!
+                    shared.set_state(RadioState::Playing);
+                    let started = Instant::now();
 
-                // Now playing!
-                state.store(STATE_PLAYING, Ordering::SeqCst);
+                    // Keep thread alive, poll volume changes until stopped or the
+                    // stream runs dry (EOF, HTTP error or read timeout)
+                    while !shared.stopped() && !sink.empty() {
+                        shared.apply_volume(sink);
+                        thread::sleep(Duration::from_millis(50));
+                    }
 
-                // Keep thread alive, poll volume changes
-                while stop_flag.load(Ordering::SeqCst) == 0 {
-                    let vol = volume.load(Ordering::SeqCst) as f32 / 100.0;
-                    sink.set_volume(vol);
-                    thread::sleep(std::time::Duration::from_millis(50));
+                    // A stream that played for a while gets a fresh set of retries
+                    if started.elapsed() >= retry.stable_after {
+                        failures = 0;
+                    }
+                    RadioError::new(ErrorKind::Network, "stream ended unexpectedly")
                 }
+                Err(e) => e,
+            };
+
+            if shared.stopped() {
+                break;
+            }
+
+            failures += 1;
+            if failures > retry.max_retries || !err.is_transient() {
+                shared.set_state(RadioState::Error(err));
+                break;
+            }
 
-                sink.stop();
+            shared.set_title(None);
+            shared.set_state(RadioState::Reconnecting {
+                attempt: failures,
+                last_error: err,
             });
 
-            drop(sink);
+            let wait_until = Instant::now() + retry.backoff(failures, &mut rng);
+            while Instant::now() < wait_until && !shared.stopped() {
+                thread::sleep(Duration::from_millis(50));
+            }
+            if shared.stopped() {
+                break;
+            }
+        }
+    });
+}
+
+fn play_noise(sink: &Sink, noise: Noise, shared: &Shared) {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    sink.append(shared.process(NoiseSource::new(noise, seed)));
+    shared.apply_volume(sink);
+    sink.play();
+    shared.set_state(RadioState::Playing);
 
-            // Suppress rodio's "Dropping OutputStream" message
-            unsafe {
-                let null = File::open("/dev/null").ok();
-                let old_stderr = libc::dup(2);
-                if let Some(ref f) = null {
-                    libc::dup2(f.as_raw_fd(), 2);
+    while !shared.stopped() {
+        shared.apply_volume(sink);
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Plays every audio file in a folder, one at a time, until stopped.
+fn play_folder(sink: &Sink, dir: &Path, shuffle: bool, shared: &Shared) {
+    let mut tracks = local::scan(dir);
+    if tracks.is_empty() {
+        let err = RadioError::new(
+            ErrorKind::NoTracks,
+            format!("no audio files found in {}", dir.display()),
+        );
+        shared.set_state(RadioState::Error(err));
+        return;
+    }
+    if shuffle {
+        Rng::from_time().shuffle(&mut tracks);
+    }
+
+    let mut index = 0;
+    let mut failed = 0;
+    // Broken files are skipped in the direction the user was going
+    let mut step = 1;
+    while !shared.stopped() {
+        let track = &tracks[index];
+
+        match local::open(track) {
+            Ok(source) => {
+                failed = 0;
+                step = 1;
+                sink.append(shared.process(source));
+                shared.apply_volume(sink);
+                sink.play();
+                shared.set_title(Some(local::track_name(track)));
+                shared.set_state(RadioState::Playing);
+
+                while !shared.stopped() && !sink.empty() {
+                    shared.apply_volume(sink);
+                    let skip = shared.skip.swap(0, Ordering::SeqCst);
+                    if skip != 0 {
+                        step = skip as isize;
+                        sink.skip_one();
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(50));
                 }
-                drop(audio_stream);
-                if old_stderr >= 0 {
-                    libc::dup2(old_stderr, 2);
-                    libc::close(old_stderr);
+            }
+            Err(message) => {
+                // Skip files that don't decode, unless none of them do
+                failed += 1;
+                if failed >= tracks.len() {
+                    let err = RadioError::new(ErrorKind::UnsupportedFormat, message);
+                    shared.set_state(RadioState::Error(err));
+                    return;
                 }
             }
-        });
+        }
 
-        self.handle = Some(handle);
+        index = (index as isize + step).rem_euclid(tracks.len() as isize) as usize;
     }
+}
 
-    pub fn stop(&mut self) {
-        self.stop_flag.store(1, Ordering::SeqCst);
-        if let Some(handle) = self.handle.take() {
-            let _ = handle.join();
+type StreamSource = Box<dyn Source + Send>;
+
+/// Opens a station url. M3U/PLS playlists are expanded and each entry is
+/// tried in order until one decodes; the last failure is returned. `None`
+/// if playback was stopped meanwhile.
+async fn open_source(
+    client: &reqwest::Client,
+    station: &Station,
+    shared: &Shared,
+) -> Result<Option<StreamSource>, RadioError> {
+    let url: reqwest::Url = station
+        .url
+        .parse()
+        .map_err(|e| RadioError::new(ErrorKind::InvalidUrl, format!("invalid url: {}", e)))?;
+
+    let candidates = if playlist::is_playlist_url(&url) {
+        fetch_playlist(client, &url).await?
+    } else {
+        let http_stream = HttpStream::new(client.clone(), url.clone())
+            .await
+            .map_err(|e| stream_error(&e))?;
+        let is_playlist = http_stream.content_type().as_ref().is_some_and(|ct| {
+            playlist::is_playlist_content_type(&format!("{}/{}", ct.r#type, ct.subtype))
+        });
+        if !is_playlist {
+            return decode_stream(http_stream, station, shared).await;
         }
-        self.state.store(STATE_STOPPED, Ordering::SeqCst);
-    }
+        // Not every directory uses a playlist extension, so fetch it as text
+        drop(http_stream);
+        fetch_playlist(client, &url).await?
+    };
 
-    pub fn next_station(&mut self) {
-        let was_active = self.is_playing() || self.is_loading();
-        self.stop();
-        self.current_station = (self.current_station + 1) % STATIONS.len();
-        if was_active {
-            self.play();
+    let mut last_err = RadioError::new(ErrorKind::Playlist, "playlist has no stream urls");
+    for candidate in candidates {
+        if shared.stopped() {
+            return Ok(None);
+        }
+        let http_stream = match HttpStream::new(client.clone(), candidate).await {
+            Ok(s) => s,
+            Err(e) => {
+                last_err = stream_error(&e);
+                continue;
+            }
+        };
+        match decode_stream(http_stream, station, shared).await {
+            Ok(source) => return Ok(source),
+            Err(e) => last_err = e,
         }
     }
 
-    pub fn prev_station(&mut self) {
-        let was_active = self.is_playing() || self.is_loading();
-        self.stop();
-        self.current_station = if self.current_station == 0 {
-            STATIONS.len() - 1
-        } else {
-            self.current_station - 1
-        };
-        if was_active {
-            self.play();
+    Err(last_err)
+}
+
+async fn fetch_playlist(
+    client: &reqwest::Client,
+    url: &reqwest::Url,
+) -> Result<Vec<reqwest::Url>, RadioError> {
+    playlist::fetch(client, url)
+        .await
+        .map_err(|e| RadioError::from_reqwest(&e))
+}
+
+fn stream_error(err: &HttpStreamError<reqwest::Client>) -> RadioError {
+    match err {
+        HttpStreamError::FetchFailure(e) => RadioError::from_reqwest(e),
+        HttpStreamError::ResponseFailure(e) => RadioError::from_reqwest(e.source()),
+    }
+}
+
+async fn decode_stream(
+    http_stream: HttpStream<reqwest::Client>,
+    station: &Station,
+    shared: &Shared,
+) -> Result<Option<StreamSource>, RadioError> {
+    let metaint = http_stream
+        .header("icy-metaint")
+        .and_then(|v| v.trim().parse().ok());
+    let content_type = http_stream
+        .content_type()
+        .as_ref()
+        .map(|ct| format!("{}/{}", ct.r#type, ct.subtype));
+    shared
+        .recorder
+        .lock()
+        .unwrap()
+        .set_stream(&station.name, content_type.as_deref());
+
+    let reader =
+        StreamDownload::from_stream(http_stream, TempStorageProvider::new(), Settings::default())
+            .await
+            .map_err(|e| RadioError::new(ErrorKind::Storage, e.to_string()))?;
+
+    if shared.stopped() {
+        return Ok(None);
+    }
+
+    let mut reader = IcyReader::new(
+        reader,
+        metaint,
+        shared.title.clone(),
+        shared.recorder.clone(),
+    );
+    if is_opus(&mut reader) {
+        let source = OpusSource::new(reader)
+            .map_err(|e| RadioError::new(ErrorKind::UnsupportedFormat, e))?;
+        return Ok(Some(Box::new(source)));
+    }
+    let decoder = Decoder::new(reader).map_err(|e| match e {
+        DecoderError::UnrecognizedFormat | DecoderError::NoStreams => {
+            RadioError::new(ErrorKind::UnsupportedFormat, e.to_string())
+        }
+        _ => RadioError::new(ErrorKind::Decode, e.to_string()),
+    })?;
+    Ok(Some(Box::new(decoder)))
+}
+
+/// True if the stream starts with an Ogg Opus header. Servers often send
+/// plain `audio/ogg` for Opus, and rodio's decoders can't play it.
+fn is_opus<R: Read + Seek>(reader: &mut R) -> bool {
+    let mut head = [0u8; 64];
+    let mut len = 0;
+    while len < head.len() {
+        match reader.read(&mut head[len..]) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => len += n,
         }
     }
+    let _ = reader.seek(SeekFrom::Start(0));
+    head[..len].starts_with(b"OggS") && head[..len].windows(8).any(|w| w == b"OpusHead")
 }
 
 impl Drop for Radio {
@@ -262,3 +1388,672 @@ impl Drop for Radio {
         self.stop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::OutputTarget;
+    use crate::testing;
+    use std::io::Write;
+    use std::net::SocketAddr;
+    use std::sync::atomic::AtomicUsize;
+
+    fn shared() -> Shared {
+        Shared {
+            state: Arc::new(Mutex::new(RadioState::Loading)),
+            stop_flag: Arc::new(AtomicU8::new(0)),
+            volume: Arc::new(AtomicU8::new(100)),
+            muted: Arc::new(AtomicBool::new(false)),
+            normalize: Arc::new(AtomicBool::new(false)),
+            target_db: -18.0,
+            gain_db: Arc::new(AtomicU32::new(0f32.to_bits())),
+            fade: Arc::new(Mutex::new(Fade::Full)),
+            title: Arc::new(Mutex::new(None)),
+            skip: Arc::new(AtomicI8::new(0)),
+            recorder: Arc::new(Mutex::new(Recorder::new())),
+        }
+    }
+
+    fn station(url: String) -> Station {
+        Station {
+            name: "Test".to_string(),
+            url,
+            ..Default::default()
+        }
+    }
+
+    /// A radio on a null output, which has to outlive it.
+    fn radio(url: String) -> (Radio, Output) {
+        let output = Output::open(&OutputTarget::Null);
+        let radio = Radio::new(
+            vec![station(url)],
+            RadioConfig::default(),
+            NetworkConfig::default(),
+            LoudnessConfig::default(),
+            &[],
+            &output,
+        );
+        (radio, output.unwrap())
+    }
+
+    /// Two stations at `/a` and `/b` of `addr`, with a short crossfade.
+    fn switching_radio(addr: SocketAddr) -> (Radio, Output) {
+        let output = Output::open(&OutputTarget::Null);
+        let stations = ["a", "b"]
+            .map(|path| Station {
+                name: path.to_string(),
+                url: format!("http://{}/{}", addr, path),
+                ..Default::default()
+            })
+            .to_vec();
+        let config = RadioConfig {
+            crossfade_secs: 0.3,
+            ..Default::default()
+        };
+        let radio = Radio::new(
+            stations,
+            config,
+            NetworkConfig::default(),
+            LoudnessConfig::default(),
+            &[],
+            &output,
+        );
+        (radio, output.unwrap())
+    }
+
+    fn wait_for(what: &str, timeout: Duration, mut done: impl FnMut() -> bool) {
+        let end = Instant::now() + timeout;
+        while !done() {
+            assert!(Instant::now() < end, "timed out waiting for {}", what);
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    fn open(url: String) -> Result<Option<StreamSource>, RadioError> {
+        let client = net::client(&NetworkConfig::default(), Default::default()).unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(open_source(&client, &station(url), &shared()))
+    }
+
+    #[test]
+    fn playlists_fall_through_to_a_working_entry() {
+        let dead = testing::dead_addr();
+        let addr = testing::serve(move |request, mut stream| {
+            let live = stream.local_addr().unwrap();
+            let fixture = match request.target() {
+                "/stations.pls" => include_str!("../tests/fixtures/stations.pls"),
+                "/stations.m3u" => include_str!("../tests/fixtures/stations.m3u"),
+                "/stream.wav" => {
+                    testing::respond(
+                        &mut stream,
+                        &[("Content-Type", "audio/wav")],
+                        &testing::wav(1.0),
+                    );
+                    return;
+                }
+                _ => return,
+            };
+            let body = fixture
+                .replace("{dead}", &dead.to_string())
+                .replace("{live}", &live.to_string());
+            testing::respond(
+                &mut stream,
+                &[("Content-Type", "audio/x-mpegurl")],
+                body.as_bytes(),
+            );
+        });
+
+        for playlist in ["stations.pls", "stations.m3u"] {
+            let source = open(format!("http://{}/{}", addr, playlist))
+                .unwrap_or_else(|e| panic!("{}: {}", playlist, e.message))
+                .unwrap();
+            assert_eq!(source.sample_rate(), testing::WAV_RATE);
+        }
+    }
+
+    #[test]
+    fn opus_streams_play() {
+        let addr = testing::serve(|_, mut stream| {
+            testing::respond(
+                &mut stream,
+                &[("Content-Type", "audio/ogg")],
+                &testing::opus(1.0, 2, 2),
+            );
+        });
+        let source = open(format!("http://{}/stream.opus", addr))
+            .unwrap_or_else(|e| panic!("{}", e.message))
+            .unwrap();
+        assert_eq!(source.sample_rate(), 48_000);
+        assert_eq!(source.channels(), 2);
+        assert!(source.count() > 48_000);
+    }
+
+    #[test]
+    fn playlists_report_the_last_failure() {
+        let dead = testing::dead_addr();
+        let addr = testing::serve(move |_, mut stream| {
+            let body = format!("http://{}/a.mp3\nhttp://{}/b.mp3\n", dead, dead);
+            testing::respond(
+                &mut stream,
+                &[("Content-Type", "audio/x-mpegurl")],
+                body.as_bytes(),
+            );
+        });
+
+        let err = open(format!("http://{}/stations.m3u", addr)).err().unwrap();
+        assert!(err.is_transient(), "{}", err.message);
+    }
+
+    #[test]
+    fn stream_titles_follow_the_metadata() {
+        // Half a second of audio between blocks, the title changes 3s in
+        let metaint = testing::WAV_RATE as usize;
+        let mut blocks = vec![""; 10];
+        blocks[0] = "StreamTitle='First';";
+        blocks[5] = "StreamTitle='Second';";
+        let body = testing::icy(&testing::wav(5.0), metaint, &blocks);
+        let addr = testing::serve(move |_, mut stream| {
+            let headers = [
+                ("Content-Type", "audio/wav"),
+                ("icy-metaint", &metaint.to_string()),
+            ];
+            testing::respond(&mut stream, &headers, &body);
+        });
+
+        let (mut radio, _output) = radio(format!("http://{}/stream", addr));
+        radio.play();
+        wait_for("the first title", Duration::from_secs(5), || {
+            radio.now_playing() == Some(("Test".to_string(), "First".to_string()))
+        });
+        wait_for("the second title", Duration::from_secs(5), || {
+            radio.now_playing() == Some(("Test".to_string(), "Second".to_string()))
+        });
+    }
+
+    fn fast_retry(max_retries: u32, stable_after: Duration) -> Retry {
+        Retry {
+            max_retries,
+            stable_after,
+            base: Duration::from_millis(50),
+            max: Duration::from_millis(100),
+        }
+    }
+
+    /// Serves a WAV that claims to be long but is cut off after `secs`,
+    /// counting connections.
+    fn dropping_server(secs: f32) -> (SocketAddr, Arc<AtomicUsize>) {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        let mut body = testing::wav(60.0);
+        body.truncate(44 + (secs * testing::WAV_RATE as f32) as usize * 2);
+        let addr = testing::serve(move |_, mut stream| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: audio/wav\r\n\r\n");
+            let _ = stream.write_all(&body);
+        });
+        (addr, connections)
+    }
+
+    /// Attempt numbers of every `Reconnecting` state seen, until `done`.
+    fn watch(radio: &Radio, timeout: Duration, done: impl Fn(&RadioState) -> bool) -> Vec<u32> {
+        let mut attempts = Vec::new();
+        let mut reconnecting = false;
+        wait_for("the radio", timeout, || {
+            let state = radio.state();
+            match state {
+                RadioState::Reconnecting { attempt, .. } if !reconnecting => {
+                    attempts.push(attempt);
+                    reconnecting = true;
+                }
+                RadioState::Reconnecting { .. } => {}
+                _ => reconnecting = false,
+            }
+            done(&state)
+        });
+        attempts
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let retry = Retry::new(5);
+        let mut rng = Rng::new(7);
+        for (attempt, secs) in [
+            (1, 1.0),
+            (2, 2.0),
+            (3, 4.0),
+            (5, 16.0),
+            (6, 30.0),
+            (40, 30.0),
+        ] {
+            for _ in 0..50 {
+                let delay = retry.backoff(attempt, &mut rng).as_secs_f64();
+                assert!(
+                    (secs * 0.75..=secs * 1.25).contains(&delay),
+                    "attempt {}: {}s",
+                    attempt,
+                    delay
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_jitter_comes_from_the_rng() {
+        let retry = Retry::new(5);
+        let delays = |seed| {
+            let mut rng = Rng::new(seed);
+            (1..8)
+                .map(|attempt| retry.backoff(attempt, &mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(delays(1), delays(1));
+        assert_ne!(delays(1), delays(2));
+    }
+
+    #[test]
+    fn dropped_streams_give_up_after_max_retries() {
+        let (addr, connections) = dropping_server(0.2);
+        let (mut radio, _output) = radio(format!("http://{}/stream", addr));
+        radio.retry = fast_retry(2, Duration::from_secs(60));
+        radio.play();
+
+        let attempts = watch(&radio, Duration::from_secs(10), |state| {
+            matches!(state, RadioState::Error(_))
+        });
+        assert_eq!(attempts, [1, 2]);
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn stable_playback_resets_the_retry_count() {
+        let (addr, connections) = dropping_server(0.5);
+        let (mut radio, _output) = radio(format!("http://{}/stream", addr));
+        radio.retry = fast_retry(2, Duration::from_millis(200));
+        radio.play();
+
+        // Well past max_retries drops, each after playing long enough to count as stable
+        let attempts = watch(&radio, Duration::from_secs(10), |state| {
+            assert!(!matches!(state, RadioState::Error(_)), "gave up");
+            connections.load(Ordering::SeqCst) >= 5
+        });
+        assert!(attempts.len() >= 3);
+        assert!(attempts.iter().all(|&a| a == 1), "{:?}", attempts);
+        radio.stop();
+    }
+
+    #[test]
+    fn invalid_urls_are_not_retried() {
+        let err = open("not a url".to_string()).err().unwrap();
+        assert!(matches!(err.kind, ErrorKind::InvalidUrl));
+        assert!(!err.is_transient());
+    }
+
+    #[test]
+    fn retries_follow_the_status_code() {
+        let addr = testing::serve(|request, mut stream| {
+            let head = format!(
+                "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                &request.target()[1..]
+            );
+            let _ = stream.write_all(head.as_bytes());
+        });
+        for (status, transient) in [(503, true), (429, true), (404, false), (403, false)] {
+            let err = open(format!("http://{}/{}", addr, status)).err().unwrap();
+            assert!(matches!(err.kind, ErrorKind::Http(s) if s == status));
+            assert_eq!(err.is_transient(), transient, "HTTP {}", status);
+        }
+    }
+
+    #[test]
+    fn refused_connections_are_retried() {
+        let err = open(format!("http://{}/stream", testing::dead_addr()))
+            .err()
+            .unwrap();
+        assert!(matches!(err.kind, ErrorKind::Network), "{}", err.message);
+        assert!(err.is_transient());
+    }
+
+    #[test]
+    fn untrusted_certificates_are_not_retried() {
+        let addr = testing::serve_tls(b"");
+        let err = open(format!("https://localhost:{}/stream", addr.port()))
+            .err()
+            .unwrap();
+        assert!(matches!(err.kind, ErrorKind::Tls), "{}", err.message);
+        assert!(!err.is_transient());
+    }
+
+    #[test]
+    fn a_missing_ca_bundle_stops_the_station() {
+        let path = std::env::temp_dir().join(format!("loshell-no-ca-{}.pem", std::process::id()));
+        let network = NetworkConfig {
+            ca_bundles: vec![path.clone()],
+            ..Default::default()
+        };
+        let output = Output::open(&OutputTarget::Null);
+        let mut radio = Radio::new(
+            vec![station(format!("http://{}/stream", testing::dead_addr()))],
+            RadioConfig::default(),
+            network,
+            LoudnessConfig::default(),
+            &[],
+            &output,
+        );
+        radio.play();
+        wait_for("the error", Duration::from_secs(5), || {
+            radio.error().is_some()
+        });
+        let err = radio.error().unwrap();
+        assert!(matches!(err.kind, ErrorKind::Config));
+        assert!(
+            err.message.contains(&path.display().to_string()),
+            "{}",
+            err.message
+        );
+    }
+
+    #[test]
+    fn stopping_while_connecting_is_not_an_error() {
+        let addr = testing::serve(|_, mut stream| {
+            testing::respond(
+                &mut stream,
+                &[("Content-Type", "audio/wav")],
+                &testing::wav(1.0),
+            );
+        });
+        let shared = shared();
+        shared.stop_flag.store(1, Ordering::SeqCst);
+
+        let client = net::client(&NetworkConfig::default(), Default::default()).unwrap();
+        let station = station(format!("http://{}/stream", addr));
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let opened = rt.block_on(open_source(&client, &station, &shared));
+        assert!(matches!(opened, Ok(None)));
+    }
+
+    fn level(radio: &Radio) -> f32 {
+        // Let a fresh window of the mix through first
+        thread::sleep(Duration::from_millis(150));
+        let scope = radio.scope().unwrap();
+        let sum: f32 = scope.samples.iter().map(|s| s * s).sum();
+        (sum / scope.samples.len().max(1) as f32).sqrt()
+    }
+
+    fn layered_radio() -> (Radio, Output) {
+        let output = Output::open(&OutputTarget::Null);
+        let layer = LayerConfig {
+            noise: Noise::White,
+            volume: 100,
+            muted: false,
+            enabled: true,
+        };
+        let radio = Radio::new(
+            builtin_stations(),
+            RadioConfig::default(),
+            NetworkConfig::default(),
+            LoudnessConfig::default(),
+            &[layer],
+            &output,
+        );
+        (radio, output.unwrap())
+    }
+
+    #[test]
+    fn layers_are_ducked_with_the_station() {
+        let (mut radio, _output) = layered_radio();
+        let full = level(&radio);
+        assert!(full > 0.05, "layer not in the mix: {}", full);
+        // The station is stopped, but the layer still needs ducking
+        assert!(!radio.is_active());
+        assert!(radio.is_audible());
+
+        radio.duck(&DuckConfig {
+            depth: 0.1,
+            hold_secs: 5.0,
+            fade_secs: 0.05,
+            curve: DuckCurve::Linear,
+        });
+        let ducked = level(&radio);
+        assert!(ducked < full * 0.2, "{} -> {}", full, ducked);
+
+        radio.mixer.layers[0].toggle_mute();
+        assert!(!radio.is_audible());
+    }
+
+    #[test]
+    fn the_sleep_timer_fades_and_stops_layers() {
+        let (mut radio, _output) = layered_radio();
+        let full = level(&radio);
+
+        // Almost at the end of the fade-out already
+        radio.sleep = Some(Sleep::At(Instant::now() + Duration::from_millis(300)));
+        radio.tick();
+        assert!(level(&radio) < full * 0.2);
+
+        thread::sleep(Duration::from_millis(200));
+        radio.tick();
+        assert!(radio.sleep().is_none());
+        assert!(!radio.mixer.layers[0].is_enabled());
+
+        // Turned back on afterwards, it plays at full level again
+        radio.tick();
+        radio.mixer.layers[0].toggle_enabled();
+        assert!(level(&radio) > full * 0.5);
+    }
+
+    #[test]
+    fn a_bad_station_entry_is_reported_and_the_rest_load() {
+        let custom: Vec<serde_json::Value> = serde_json::from_str(
+            r#"[
+                {"name": "Good", "url": "https://example.com/good"},
+                {"name": "Null", "url": null},
+                {"name": "Genre", "url": "https://example.com/g", "genre": 5},
+                {"url": "https://example.com/q", "quality": "ultra"},
+                {"name": "Also good", "url": "https://example.com/also"}
+            ]"#,
+        )
+        .unwrap();
+        let (stations, warnings) = load_stations(&custom, StationMode::Replace);
+
+        let names: Vec<&str> = stations.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Good", "Also good"]);
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("\"Null\""), "{}", warnings[0]);
+        assert!(warnings[1].contains("\"Genre\""), "{}", warnings[1]);
+        assert!(warnings[2].contains("#4"), "{}", warnings[2]);
+    }
+
+    #[test]
+    fn switching_crossfades_once_the_new_station_plays() {
+        let released = Arc::new(AtomicBool::new(false));
+        let gate = released.clone();
+        let body = testing::wav(30.0);
+        let addr = testing::serve(move |request, mut stream| {
+            // The new station answers only once the test lets it
+            while request.target() == "/b" && !gate.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(20));
+            }
+            testing::respond(&mut stream, &[("Content-Type", "audio/wav")], &body);
+        });
+        let (mut radio, _output) = switching_radio(addr);
+        radio.play();
+        wait_for("the first station", Duration::from_secs(5), || {
+            radio.is_playing()
+        });
+
+        radio.next_station();
+        thread::sleep(Duration::from_millis(300));
+        assert!(radio.tick().is_none());
+        let current = radio.current.as_ref().unwrap();
+        assert_eq!(current.station, 0);
+        assert!(matches!(current.state(), RadioState::Playing));
+        assert!(matches!(current.fade(), Fade::Full));
+        assert!(radio.pending.is_some());
+
+        released.store(true, Ordering::SeqCst);
+        wait_for("the crossfade", Duration::from_secs(5), || {
+            assert!(radio.tick().is_none());
+            radio.pending.is_none()
+        });
+        let current = radio.current.as_ref().unwrap();
+        assert_eq!(current.station, 1);
+        assert!(matches!(current.fade(), Fade::In { .. }));
+        let fading = radio.fading.as_ref().unwrap();
+        assert_eq!(fading.station, 0);
+        assert!(matches!(fading.fade(), Fade::Out { .. }));
+
+        wait_for("the old station to stop", Duration::from_secs(5), || {
+            radio.tick();
+            radio.fading.is_none()
+        });
+        assert_eq!(radio.current_station, 1);
+        assert!(radio.is_playing());
+    }
+
+    #[test]
+    fn a_station_that_fails_leaves_the_old_one_playing() {
+        let body = testing::wav(30.0);
+        let addr = testing::serve(move |request, mut stream| {
+            if request.target() == "/a" {
+                testing::respond(&mut stream, &[("Content-Type", "audio/wav")], &body);
+            } else {
+                let _ = stream.write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            }
+        });
+        let (mut radio, _output) = switching_radio(addr);
+        radio.play();
+        wait_for("the first station", Duration::from_secs(5), || {
+            radio.is_playing()
+        });
+
+        radio.next_station();
+        let mut failure = None;
+        wait_for("the switch to fail", Duration::from_secs(5), || {
+            failure = radio.tick();
+            failure.is_some()
+        });
+        let failure = failure.unwrap();
+        assert_eq!(failure.station, "b");
+        assert!(matches!(failure.error.kind, ErrorKind::Http(404)));
+
+        assert!(radio.pending.is_none());
+        assert_eq!(radio.current_station, 0);
+        let current = radio.current.as_ref().unwrap();
+        assert_eq!(current.station, 0);
+        assert!(matches!(current.fade(), Fade::Full));
+        assert!(radio.is_playing());
+    }
+
+    /// A radio playing a folder of `(file name, contents)`, on a null output.
+    fn folder_radio(name: &str, files: &[(&str, &[u8])]) -> (Radio, Output, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("loshell-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            std::fs::write(dir.join(file), contents).unwrap();
+        }
+        let output = Output::open(&OutputTarget::Null);
+        let station = Station {
+            name: "Folder".to_string(),
+            path: Some(dir.clone()),
+            ..Default::default()
+        };
+        let radio = Radio::new(
+            vec![station],
+            RadioConfig::default(),
+            NetworkConfig::default(),
+            LoudnessConfig::default(),
+            &[],
+            &output,
+        );
+        (radio, output.unwrap(), dir)
+    }
+
+    fn wait_for_track(radio: &Radio, track: &str) {
+        wait_for(track, Duration::from_secs(5), || {
+            radio.now_playing().is_some_and(|(_, title)| title == track)
+        });
+    }
+
+    #[test]
+    fn folders_skip_broken_files_and_wrap_around() {
+        let wav = testing::wav(10.0);
+        let (mut radio, _output, dir) = folder_radio(
+            "folder",
+            &[("a.wav", &wav), ("b.wav", b"not audio"), ("c.wav", &wav)],
+        );
+        radio.play();
+        wait_for_track(&radio, "a");
+
+        radio.next_track();
+        wait_for_track(&radio, "c");
+        radio.next_track();
+        wait_for_track(&radio, "a");
+        radio.prev_track();
+        wait_for_track(&radio, "c");
+        // Going back past the broken file too
+        radio.prev_track();
+        wait_for_track(&radio, "a");
+
+        radio.stop();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn a_folder_of_broken_files_is_an_error() {
+        let (mut radio, _output, dir) =
+            folder_radio("folder-broken", &[("a.mp3", b"junk"), ("b.wav", b"junk")]);
+        radio.play();
+        wait_for("the error", Duration::from_secs(5), || {
+            radio.error().is_some()
+        });
+        assert!(matches!(
+            radio.error().unwrap().kind,
+            ErrorKind::UnsupportedFormat
+        ));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn stopping_does_not_wait_for_a_station_still_connecting() {
+        let body = testing::wav(30.0);
+        let addr = testing::serve(move |request, mut stream| {
+            // The new station never answers
+            if request.target() == "/b" {
+                thread::sleep(Duration::from_secs(30));
+            }
+            testing::respond(&mut stream, &[("Content-Type", "audio/wav")], &body);
+        });
+        let (mut radio, _output) = switching_radio(addr);
+        radio.play();
+        wait_for("the first station", Duration::from_secs(5), || {
+            radio.is_playing()
+        });
+        radio.next_station();
+        thread::sleep(Duration::from_millis(200));
+        assert!(radio.pending.is_some());
+
+        let start = Instant::now();
+        radio.stop();
+        assert!(
+            start.elapsed() < Duration::from_secs(1),
+            "{:?}",
+            start.elapsed()
+        );
+        assert!(!radio.is_active());
+
+        // Same for a station that never got to play
+        assert_eq!(radio.current_station, 1);
+        radio.play();
+        thread::sleep(Duration::from_millis(200));
+        let start = Instant::now();
+        radio.stop();
+        assert!(
+            start.elapsed() < Duration::from_secs(1),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+}
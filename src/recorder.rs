@@ -0,0 +1,225 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::clock::LocalTime;
+use crate::storage;
+
+/// Tees stream audio bytes into files under the recordings folder, starting a
+/// new file whenever the ICY track title changes.
+pub struct Recorder {
+    recording: bool,
+    dir: PathBuf,
+    station: String,
+    extension: &'static str,
+    title: Option<String>,
+    file: Option<BufWriter<File>>,
+    bytes: u64,
+    started: Option<Instant>,
+    // Why recording stopped on its own, until the UI picks it up
+    error: Option<String>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self {
+            recording: false,
+            dir: storage::recordings_dir(),
+            station: String::new(),
+            extension: "mp3",
+            title: None,
+            file: None,
+            bytes: 0,
+            started: None,
+            error: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Bytes written since recording started.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn elapsed_secs(&self) -> u64 {
+        self.started.map(|s| s.elapsed().as_secs()).unwrap_or(0)
+    }
+
+    /// The error that stopped recording, once.
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error.take()
+    }
+
+    pub fn start(&mut self) {
+        self.recording = true;
+        self.bytes = 0;
+        self.started = Some(Instant::now());
+    }
+
+    /// Flushes and closes the current file.
+    pub fn stop(&mut self) {
+        self.recording = false;
+        self.started = None;
+        self.close_file();
+    }
+
+    /// Called when a new stream connects.
+    pub fn set_stream(&mut self, station: &str, content_type: Option<&str>) {
+        self.close_file();
+        self.station = station.to_string();
+        self.extension = extension_for(content_type);
+        self.title = None;
+    }
+
+    /// Starts a new file on the next write when the track changes.
+    pub fn set_title(&mut self, title: Option<String>) {
+        if title != self.title {
+            self.close_file();
+            self.title = title;
+        }
+    }
+
+    pub fn write(&mut self, data: &[u8]) {
+        if !self.recording || data.is_empty() {
+            return;
+        }
+        if let Err(e) = self.write_file(data) {
+            // Don't try again on every read
+            self.stop();
+            self.error = Some(e);
+        }
+    }
+
+    fn write_file(&mut self, data: &[u8]) -> Result<(), String> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(self.open_file()?),
+        };
+        file.write_all(data).map_err(|e| e.to_string())?;
+        self.bytes += data.len() as u64;
+        Ok(())
+    }
+
+    fn open_file(&self) -> Result<BufWriter<File>, String> {
+        fs::create_dir_all(&self.dir).map_err(|e| format!("{}: {}", self.dir.display(), e))?;
+
+        let mut name = format!("{} {}", LocalTime::now().file_stamp(), self.station);
+        if let Some(title) = &self.title {
+            name.push_str(" - ");
+            name.push_str(title);
+        }
+        let path: PathBuf = self
+            .dir
+            .join(format!("{}.{}", sanitize(&name), self.extension));
+        let file = File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(BufWriter::new(file))
+    }
+
+    fn close_file(&mut self) {
+        if let Some(mut file) = self.file.take() {
+            let _ = file.flush();
+        }
+    }
+}
+
+fn extension_for(content_type: Option<&str>) -> &'static str {
+    match content_type.map(|c| c.to_ascii_lowercase()) {
+        Some(c) if c.contains("aac") || c.contains("mp4") => "aac",
+        Some(c) if c.contains("ogg") || c.contains("opus") => "ogg",
+        Some(c) if c.contains("flac") => "flac",
+        _ => "mp3",
+    }
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icy::IcyReader;
+    use crate::testing;
+    use std::io::{Cursor, Read};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn failing_to_create_a_file_stops_with_an_error() {
+        // A file where the folder should be
+        let blocker = std::env::temp_dir().join(format!("loshell-rec-{}", std::process::id()));
+        fs::write(&blocker, b"").unwrap();
+
+        let mut recorder = Recorder::new();
+        recorder.dir = blocker.join("recordings");
+        recorder.set_stream("Test", Some("audio/mpeg"));
+        recorder.start();
+        recorder.write(b"audio");
+
+        assert!(!recorder.is_recording());
+        assert_eq!(recorder.bytes(), 0);
+        let error = recorder.take_error().unwrap();
+        assert!(error.contains("recordings"), "{}", error);
+        assert!(recorder.take_error().is_none());
+        let _ = fs::remove_file(blocker);
+    }
+
+    #[test]
+    fn each_title_gets_its_own_file() {
+        let dir = std::env::temp_dir().join(format!("loshell-rec-titles-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut recorder = Recorder::new();
+        recorder.dir = dir.clone();
+        recorder.set_stream("Test", Some("audio/mpeg"));
+        recorder.start();
+        let recorder = Arc::new(Mutex::new(recorder));
+
+        let body = testing::icy(
+            b"aaaabbbbccccdddd",
+            4,
+            &["StreamTitle='One';", "", "StreamTitle='Two/Part';", ""],
+        );
+        let mut reader = IcyReader::new(
+            Cursor::new(body),
+            Some(4),
+            Arc::new(Mutex::new(None)),
+            recorder.clone(),
+        );
+        reader.read_to_end(&mut Vec::new()).unwrap();
+        recorder.lock().unwrap().stop();
+
+        // Names start with the time, so only check the end of them
+        let mut files: Vec<(String, String)> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                let (_, name) = name.split_once(' ').unwrap();
+                (name.to_string(), fs::read_to_string(&path).unwrap())
+            })
+            .collect();
+        files.sort();
+        let _ = fs::remove_dir_all(&dir);
+
+        let expected = [
+            ("Test - One.mp3", "bbbbcccc"),
+            ("Test - Two_Part.mp3", "dddd"),
+            ("Test.mp3", "aaaa"),
+        ];
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_str()))
+            .collect();
+        assert_eq!(files, expected);
+    }
+}
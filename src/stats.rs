@@ -0,0 +1,600 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Rect},
+    text::{Line, Span},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Clear, Paragraph, Sparkline},
+};
+
+use crate::clock::{self, LocalTime};
+use crate::pomodoro::{Mode, Outcome, SessionEntry};
+use crate::storage;
+use crate::theme::Theme;
+use crate::todo::Task;
+
+const TOP_TASKS: usize = 5;
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// How much time the overlay covers at once.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    fn next(self) -> Self {
+        match self {
+            Period::Day => Period::Week,
+            Period::Week => Period::Month,
+            Period::Month => Period::Year,
+            Period::Year => Period::Day,
+        }
+    }
+}
+
+/// Calendar days from `start` up to but not including `end`, as day numbers.
+struct Range {
+    start: i64,
+    end: i64,
+    title: String,
+}
+
+/// One bar of the chart.
+struct Bucket {
+    label: String,
+    focus_secs: u64,
+    pomodoros: u64,
+}
+
+/// What the overlay shows, worked out when the view changes rather than on
+/// every frame.
+struct Summary {
+    range: Range,
+    buckets: Vec<Bucket>,
+    // Focus seconds and finished pomodoros
+    today_totals: (u64, u64),
+    range_totals: (u64, u64),
+    streaks: (u32, u32),
+    top_tasks: Vec<(u64, u64)>,
+}
+
+/// Full-screen overlay summarizing the pomodoro session log.
+pub struct Stats {
+    pub visible: bool,
+    period: Period,
+    // Periods back from the current one
+    offset: u32,
+    sessions: Vec<SessionEntry>,
+    summary: Option<Summary>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            period: Period::Week,
+            offset: 0,
+            sessions: Vec::new(),
+            summary: None,
+        }
+    }
+
+    /// Shows the overlay with a fresh read of the session log.
+    pub fn open(&mut self) {
+        self.sessions = storage::load_sessions();
+        self.visible = true;
+        self.summarize();
+    }
+
+    pub fn close(&mut self) {
+        self.visible = false;
+    }
+
+    pub fn next_period(&mut self) {
+        self.period = self.period.next();
+        self.offset = 0;
+        self.summarize();
+    }
+
+    pub fn earlier(&mut self) {
+        self.offset += 1;
+        self.summarize();
+    }
+
+    pub fn later(&mut self) {
+        self.offset = self.offset.saturating_sub(1);
+        self.summarize();
+    }
+
+    fn summarize(&mut self) {
+        let today = LocalTime::now().day_number();
+        let range = self.range(today);
+        let today_range = Range {
+            start: today,
+            end: today + 1,
+            title: String::new(),
+        };
+        self.summary = Some(Summary {
+            buckets: self.buckets(&range),
+            today_totals: totals(&self.focus_in(&today_range)),
+            range_totals: totals(&self.focus_in(&range)),
+            streaks: self.streaks(today),
+            top_tasks: self.top_tasks(&range),
+            range,
+        });
+    }
+
+    fn range(&self, today: i64) -> Range {
+        let offset = self.offset as i64;
+        match self.period {
+            Period::Day => {
+                let day = today - offset;
+                Range {
+                    start: day,
+                    end: day + 1,
+                    title: date(day),
+                }
+            }
+            Period::Week => {
+                let monday = today - clock::weekday(today) as i64 - 7 * offset;
+                Range {
+                    start: monday,
+                    end: monday + 7,
+                    title: format!("week of {}", date(monday)),
+                }
+            }
+            Period::Month => {
+                let (year, month, _) = clock::civil_from_days(today);
+                // Count months from year 0 so stepping back crosses years
+                let index = year as i64 * 12 + month as i64 - 1 - offset;
+                let first_of = |index: i64| {
+                    clock::days_from_civil(
+                        index.div_euclid(12) as i32,
+                        index.rem_euclid(12) as u32 + 1,
+                        1,
+                    )
+                };
+                Range {
+                    start: first_of(index),
+                    end: first_of(index + 1),
+                    title: format!(
+                        "{} {}",
+                        MONTHS[index.rem_euclid(12) as usize],
+                        index.div_euclid(12)
+                    ),
+                }
+            }
+            Period::Year => {
+                let year = clock::civil_from_days(today).0 - self.offset as i32;
+                Range {
+                    start: clock::days_from_civil(year, 1, 1),
+                    end: clock::days_from_civil(year + 1, 1, 1),
+                    title: year.to_string(),
+                }
+            }
+        }
+    }
+
+    /// Focus sessions that started within the range, with their local start.
+    fn focus_in(&self, range: &Range) -> Vec<(LocalTime, &SessionEntry)> {
+        self.sessions
+            .iter()
+            .filter(|s| s.mode == Mode::Focus)
+            .map(|s| (LocalTime::from_unix(s.started_at), s))
+            .filter(|(t, _)| (range.start..range.end).contains(&t.day_number()))
+            .collect()
+    }
+
+    /// Hours of a day, days of a week or month, or months of a year.
+    fn buckets(&self, range: &Range) -> Vec<Bucket> {
+        let mut buckets: Vec<Bucket> = match self.period {
+            Period::Day => (0..24).map(|h| format!("{:02}", h)).collect(),
+            Period::Week => WEEKDAYS.iter().map(|d| d.to_string()).collect::<Vec<_>>(),
+            Period::Month => (1..=range.end - range.start)
+                .map(|d| d.to_string())
+                .collect(),
+            Period::Year => MONTHS.iter().map(|m| m.to_string()).collect(),
+        }
+        .into_iter()
+        .map(|label| Bucket {
+            label,
+            focus_secs: 0,
+            pomodoros: 0,
+        })
+        .collect();
+
+        for (time, session) in self.focus_in(range) {
+            let index = match self.period {
+                Period::Day => time.hour as usize,
+                Period::Week | Period::Month => (time.day_number() - range.start) as usize,
+                Period::Year => time.month as usize - 1,
+            };
+            let bucket = &mut buckets[index];
+            bucket.focus_secs += session.actual_secs;
+            if session.outcome == Outcome::Completed {
+                bucket.pomodoros += 1;
+            }
+        }
+        buckets
+    }
+
+    /// The current run of focus days ending today or yesterday, and the
+    /// longest run ever.
+    fn streaks(&self, today: i64) -> (u32, u32) {
+        let days: BTreeSet<i64> = self
+            .sessions
+            .iter()
+            .filter(|s| s.mode == Mode::Focus && s.actual_secs > 0)
+            .map(|s| LocalTime::from_unix(s.started_at).day_number())
+            .collect();
+
+        let mut longest = 0;
+        let mut run = 0;
+        let mut previous = None;
+        for &day in &days {
+            run = if previous == Some(day - 1) {
+                run + 1
+            } else {
+                1
+            };
+            longest = longest.max(run);
+            previous = Some(day);
+        }
+
+        // Today doesn't break the streak until it's over
+        let mut day = if days.contains(&today) {
+            today
+        } else {
+            today - 1
+        };
+        let mut current = 0;
+        while days.contains(&day) {
+            current += 1;
+            day -= 1;
+        }
+        (current, longest)
+    }
+
+    /// Tasks by focus time within the range, most first. Sessions that were
+    /// reset were given up on, so they don't count.
+    fn top_tasks(&self, range: &Range) -> Vec<(u64, u64)> {
+        let mut by_task: BTreeMap<u64, u64> = BTreeMap::new();
+        for (_, session) in self.focus_in(range) {
+            if session.outcome == Outcome::Reset {
+                continue;
+            }
+            if let Some(id) = session.task_id {
+                *by_task.entry(id).or_default() += session.actual_secs;
+            }
+        }
+        let mut tasks: Vec<(u64, u64)> = by_task.into_iter().collect();
+        tasks.sort_by_key(|&(_, secs)| std::cmp::Reverse(secs));
+        tasks.truncate(TOP_TASKS);
+        tasks
+    }
+
+    pub fn draw(&self, f: &mut Frame, area: Rect, theme: &Theme, tasks: &[Task]) {
+        let Some(summary) = self.summary.as_ref().filter(|_| self.visible) else {
+            return;
+        };
+        let Summary {
+            range,
+            buckets,
+            today_totals: (today_secs, today_pomodoros),
+            range_totals: (range_secs, range_pomodoros),
+            streaks: (current, longest),
+            top_tasks,
+        } = summary;
+
+        let stats_area = Rect {
+            x: area.x + 2,
+            y: area.y + 1,
+            width: area.width.saturating_sub(4),
+            height: area.height.saturating_sub(2),
+        };
+        let block = Block::default()
+            .title(Span::styled(
+                format!(" stats: {} ", range.title),
+                theme.title(),
+            ))
+            .title_bottom(Span::styled(
+                " ←/→ earlier/later  tab day/week/month/year  esc close ",
+                theme.frame(),
+            ))
+            .borders(Borders::ALL)
+            .border_style(theme.frame());
+        let inner = block.inner(stats_area);
+        f.render_widget(Clear, stats_area);
+        f.render_widget(block.style(theme.base()), stats_area);
+
+        let [summary_area, chart_area, spark_area, tasks_area] = Layout::vertical([
+            Constraint::Length(4),
+            Constraint::Min(6),
+            Constraint::Length(3),
+            Constraint::Length(TOP_TASKS as u16 + 2),
+        ])
+        .areas(inner);
+
+        // Summary
+        let summary = Paragraph::new(vec![
+            Line::from(vec![
+                Span::styled(" today  ", theme.frame()),
+                Span::styled(format!("{} min focus", today_secs / 60), theme.accent()),
+                Span::styled(format!(", {} pomodoros", today_pomodoros), theme.base()),
+            ]),
+            Line::from(vec![
+                Span::styled(format!(" {}  ", range.title), theme.frame()),
+                Span::styled(format!("{} min focus", range_secs / 60), theme.accent()),
+                Span::styled(format!(", {} pomodoros", range_pomodoros), theme.base()),
+            ]),
+            Line::from(vec![
+                Span::styled(" streak ", theme.frame()),
+                Span::styled(format!("{} days", current), theme.hot()),
+                Span::styled(format!(", longest {} days", longest), theme.base()),
+            ]),
+        ]);
+        f.render_widget(summary, summary_area);
+
+        // Focus minutes per bucket
+        let gap = 1;
+        let bar_width = ((chart_area.width + gap) / buckets.len().max(1) as u16)
+            .saturating_sub(gap)
+            .max(1);
+        let bars: Vec<Bar> = buckets
+            .iter()
+            .map(|b| {
+                Bar::default()
+                    .value(b.focus_secs / 60)
+                    .label(Line::from(b.label.clone()))
+            })
+            .collect();
+        let chart = BarChart::default()
+            .block(
+                Block::default()
+                    .title(Span::styled(" focus minutes ", theme.frame()))
+                    .borders(Borders::TOP)
+                    .border_style(theme.frame()),
+            )
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(bar_width)
+            .bar_gap(gap)
+            .bar_style(theme.accent())
+            .value_style(theme.title())
+            .label_style(theme.frame());
+        f.render_widget(chart, chart_area);
+
+        // Finished pomodoros per bucket, stretched to line up with the bars
+        let counts: Vec<u64> = buckets
+            .iter()
+            .flat_map(|b| {
+                let mut columns = vec![b.pomodoros; bar_width as usize];
+                columns.extend(std::iter::repeat_n(0, gap as usize));
+                columns
+            })
+            .collect();
+        let sparkline = Sparkline::default()
+            .block(
+                Block::default()
+                    .title(Span::styled(" pomodoros ", theme.frame()))
+                    .borders(Borders::TOP)
+                    .border_style(theme.frame()),
+            )
+            .data(&counts)
+            .style(theme.hot());
+        f.render_widget(sparkline, spark_area);
+
+        // Top tasks, with their all-time total from the todo list
+        let mut lines = vec![Line::from(Span::styled(" top tasks", theme.frame()))];
+        if top_tasks.is_empty() {
+            lines.push(Line::from(Span::styled(
+                "  no tracked tasks in this range",
+                theme.frame(),
+            )));
+        }
+        let name_width = (tasks_area.width as usize).saturating_sub(32);
+        for &(id, secs) in top_tasks {
+            let task = tasks.iter().find(|t| t.id == id);
+            let name: String = task
+                .map_or_else(|| format!("task #{}", id), |t| t.text.clone())
+                .chars()
+                .take(name_width)
+                .collect();
+            let padding = name_width.saturating_sub(name.chars().count());
+            let total = task
+                .filter(|t| t.time_spent_secs > 0)
+                .map_or_else(String::new, |t| format!("{} total", t.format_time()));
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {}", name), theme.base()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(format!(" {:>8}", hours_minutes(secs)), theme.accent()),
+                Span::styled(format!("  {}", total), theme.frame()),
+            ]));
+        }
+        f.render_widget(Paragraph::new(lines), tasks_area);
+    }
+}
+
+/// Focus time and finished pomodoros.
+fn totals(sessions: &[(LocalTime, &SessionEntry)]) -> (u64, u64) {
+    sessions.iter().fold((0, 0), |(secs, count), (_, s)| {
+        let done = (s.outcome == Outcome::Completed) as u64;
+        (secs + s.actual_secs, count + done)
+    })
+}
+
+fn date(day: i64) -> String {
+    let (year, month, day) = clock::civil_from_days(day);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn hours_minutes(secs: u64) -> String {
+    format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::days_from_civil;
+
+    /// Unix seconds of a local date and time, whatever the time zone.
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> u64 {
+        let wanted = days_from_civil(year, month, day) * 86400 + (hour * 3600 + minute * 60) as i64;
+        let shown = LocalTime::from_unix(wanted as u64);
+        let shown = shown.day_number() * 86400
+            + (shown.hour * 3600 + shown.minute * 60 + shown.second) as i64;
+        (wanted - (shown - wanted)) as u64
+    }
+
+    fn session(mode: Mode, started_at: u64, mins: u64, outcome: Outcome) -> SessionEntry {
+        SessionEntry {
+            mode,
+            started_at,
+            ended_at: started_at + mins * 60,
+            planned_secs: 25 * 60,
+            actual_secs: mins * 60,
+            extended_secs: 0,
+            outcome,
+            task_id: None,
+        }
+    }
+
+    fn focus(started_at: u64, mins: u64) -> SessionEntry {
+        session(Mode::Focus, started_at, mins, Outcome::Completed)
+    }
+
+    fn stats(period: Period, offset: u32, sessions: Vec<SessionEntry>) -> Stats {
+        Stats {
+            visible: true,
+            period,
+            offset,
+            sessions,
+            summary: None,
+        }
+    }
+
+    /// Focus minutes per bucket label, leaving out empty buckets.
+    fn minutes(stats: &Stats, today: i64) -> Vec<(String, u64)> {
+        stats
+            .buckets(&stats.range(today))
+            .into_iter()
+            .filter(|b| b.focus_secs > 0)
+            .map(|b| (b.label, b.focus_secs / 60))
+            .collect()
+    }
+
+    #[test]
+    fn weeks_run_from_monday_to_sunday() {
+        // Wednesday, 2024-05-08
+        let today = days_from_civil(2024, 5, 8);
+        let sessions = vec![
+            focus(local(2024, 5, 5, 23, 59), 1),
+            focus(local(2024, 5, 6, 0, 0), 2),
+            focus(local(2024, 5, 12, 23, 59), 3),
+            focus(local(2024, 5, 13, 0, 0), 4),
+        ];
+
+        let week = stats(Period::Week, 0, sessions.clone());
+        let range = week.range(today);
+        assert_eq!(range.title, "week of 2024-05-06");
+        assert_eq!(week.buckets(&range).len(), 7);
+        assert_eq!(
+            minutes(&week, today),
+            [("Mo".to_string(), 2), ("Su".to_string(), 3)]
+        );
+
+        let previous = stats(Period::Week, 1, sessions);
+        assert_eq!(minutes(&previous, today), [("Su".to_string(), 1)]);
+    }
+
+    #[test]
+    fn months_have_a_bar_per_day() {
+        let today = days_from_civil(2024, 2, 15);
+        let sessions = vec![
+            focus(local(2024, 1, 31, 23, 59), 1),
+            focus(local(2024, 2, 1, 0, 0), 2),
+            focus(local(2024, 2, 29, 23, 59), 3),
+            focus(local(2024, 3, 1, 0, 0), 4),
+        ];
+
+        let february = stats(Period::Month, 0, sessions.clone());
+        let range = february.range(today);
+        assert_eq!(range.title, "Feb 2024");
+        assert_eq!(february.buckets(&range).len(), 29);
+        assert_eq!(
+            minutes(&february, today),
+            [("1".to_string(), 2), ("29".to_string(), 3)]
+        );
+
+        let january = stats(Period::Month, 1, sessions);
+        let range = january.range(today);
+        assert_eq!(january.buckets(&range).len(), 31);
+        assert_eq!(minutes(&january, today), [("31".to_string(), 1)]);
+
+        // Stepping back from January lands in the previous year
+        let december = stats(Period::Month, 1, Vec::new());
+        assert_eq!(
+            december.range(days_from_civil(2024, 1, 10)).title,
+            "Dec 2023"
+        );
+    }
+
+    #[test]
+    fn a_gap_day_ends_a_streak() {
+        let day = |d: u32| focus(local(2024, 5, d, 9, 0), 25);
+        let sessions = vec![
+            day(1),
+            day(2),
+            day(2),
+            day(3),
+            // Nothing on the 4th but a break and an empty session
+            session(Mode::Break, local(2024, 5, 4, 9, 0), 5, Outcome::Completed),
+            session(Mode::Focus, local(2024, 5, 4, 10, 0), 0, Outcome::Reset),
+            day(5),
+            day(6),
+        ];
+        let stats = stats(Period::Week, 0, sessions);
+        assert_eq!(stats.streaks(days_from_civil(2024, 5, 6)), (2, 3));
+    }
+
+    #[test]
+    fn today_does_not_break_the_streak_yet() {
+        let day = |d: u32| focus(local(2024, 5, d, 9, 0), 25);
+        let stats = stats(Period::Week, 0, vec![day(4), day(5), day(6)]);
+        assert_eq!(stats.streaks(days_from_civil(2024, 5, 7)), (3, 3));
+        // A whole day without focus does
+        assert_eq!(stats.streaks(days_from_civil(2024, 5, 8)), (0, 3));
+    }
+
+    #[test]
+    fn top_tasks_rank_focus_time() {
+        let today = days_from_civil(2024, 5, 8);
+        let at = local(2024, 5, 7, 9, 0);
+        let for_task = |mode, mins, outcome, task| SessionEntry {
+            task_id: Some(task),
+            ..session(mode, at, mins, outcome)
+        };
+        let sessions = vec![
+            for_task(Mode::Focus, 25, Outcome::Completed, 1),
+            for_task(Mode::Focus, 25, Outcome::Completed, 2),
+            for_task(Mode::Focus, 10, Outcome::Quit, 2),
+            for_task(Mode::Focus, 20, Outcome::Completed, 3),
+            // Neither breaks nor reset sessions count
+            for_task(Mode::Break, 30, Outcome::Completed, 3),
+            for_task(Mode::LongBreak, 30, Outcome::Completed, 3),
+            for_task(Mode::Focus, 60, Outcome::Reset, 4),
+            focus(at, 90),
+        ];
+        let stats = stats(Period::Week, 0, sessions);
+        assert_eq!(
+            stats.top_tasks(&stats.range(today)),
+            [(2, 35 * 60), (1, 25 * 60), (3, 20 * 60)]
+        );
+    }
+}
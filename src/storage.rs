@@ -1,8 +1,14 @@
-use std::fs;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+use crate::ambient::Noise;
+use crate::history::Track;
+use crate::pomodoro::{self, Preset, SessionEntry};
+use crate::radio::Quality;
 use crate::theme::ThemeName;
 use crate::todo::Task;
 
@@ -12,20 +18,497 @@ pub struct TaskData {
     pub next_id: u64,
 }
 
+#[derive(Serialize, Deserialize, Default)]
+pub struct HistoryData {
+    pub tracks: Vec<Track>,
+}
+
+/// Whether configured stations are added to the built-in list or replace it.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StationMode {
+    #[default]
+    Extend,
+    Replace,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RadioConfig {
+    /// Reconnect attempts after a stream drops before giving up.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Stream quality for stations that offer several.
+    #[serde(default)]
+    pub quality: Quality,
+    /// Start playing the last station on launch.
+    #[serde(default)]
+    pub autoplay: bool,
+    /// Length of the crossfade when switching stations. 0 cuts straight over.
+    #[serde(default = "default_crossfade_secs")]
+    pub crossfade_secs: f32,
+}
+
+fn default_max_retries() -> u32 {
+    5
+}
+
+fn default_crossfade_secs() -> f32 {
+    3.0
+}
+
+// Longer crossfades are cut to this
+const MAX_CROSSFADE_SECS: f32 = 30.0;
+
+impl Default for RadioConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: default_max_retries(),
+            quality: Quality::default(),
+            autoplay: false,
+            crossfade_secs: default_crossfade_secs(),
+        }
+    }
+}
+
+impl RadioConfig {
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        check_secs(
+            "crossfade_secs",
+            &mut self.crossfade_secs,
+            default_crossfade_secs(),
+            MAX_CROSSFADE_SECS,
+            warnings,
+        );
+    }
+}
+
+/// Resets a length in seconds that is negative or not a number, and cuts
+/// one that is longer than `max`.
+fn check_secs(name: &str, secs: &mut f32, default: f32, max: f32, warnings: &mut Vec<String>) {
+    let value = *secs;
+    if !value.is_finite() || value < 0.0 {
+        *secs = default;
+        warnings.push(format!("{} {} is invalid, using {}", name, value, default));
+    } else if value > max {
+        *secs = max;
+        warnings.push(format!("{} {} is too long, using {}", name, value, max));
+    }
+}
+
+/// Proxy, certificates and timeouts for everything loshell downloads.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NetworkConfig {
+    /// Proxy for http and https, e.g. `http://proxy.corp:3128`. The
+    /// `HTTP_PROXY`/`HTTPS_PROXY` variables work without this, and are
+    /// ignored with it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Hosts that skip `proxy`, like `NO_PROXY`. Defaults to `NO_PROXY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    /// PEM files with extra root certificates to trust.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ca_bundles: Vec<PathBuf>,
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// A stream that sends nothing for this long counts as dropped.
+    #[serde(default = "default_read_timeout_secs")]
+    pub read_timeout_secs: u64,
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_read_timeout_secs() -> u64 {
+    10
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            no_proxy: None,
+            ca_bundles: Vec::new(),
+            connect_timeout_secs: default_connect_timeout_secs(),
+            read_timeout_secs: default_read_timeout_secs(),
+        }
+    }
+}
+
+impl NetworkConfig {
+    // A zero timeout would fail every request straight away
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        for (name, secs, default) in [
+            (
+                "connect_timeout_secs",
+                &mut self.connect_timeout_secs,
+                default_connect_timeout_secs(),
+            ),
+            (
+                "read_timeout_secs",
+                &mut self.read_timeout_secs,
+                default_read_timeout_secs(),
+            ),
+        ] {
+            if *secs == 0 {
+                *secs = default;
+                warnings.push(format!("{} 0 is invalid, using {}", name, default));
+            }
+        }
+    }
+}
+
+/// Shape of the duck's fade down and back up.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DuckCurve {
+    Linear,
+    /// Eases in and out.
+    #[default]
+    Smooth,
+    /// Even steps in decibels, closest to how loudness is heard.
+    Exponential,
+}
+
+/// Lowers the radio while a pomodoro notification plays.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DuckConfig {
+    /// Fraction of the volume kept while ducked, 0.0-1.0.
+    #[serde(default = "default_duck_depth")]
+    pub depth: f32,
+    /// How long to stay ducked once fully down.
+    #[serde(default = "default_duck_hold_secs")]
+    pub hold_secs: f32,
+    /// Length of the fade down, and of the fade back up.
+    #[serde(default = "default_duck_fade_secs")]
+    pub fade_secs: f32,
+    #[serde(default)]
+    pub curve: DuckCurve,
+}
+
+fn default_duck_depth() -> f32 {
+    0.2
+}
+
+fn default_duck_hold_secs() -> f32 {
+    2.0
+}
+
+fn default_duck_fade_secs() -> f32 {
+    3.0
+}
+
+// Fades and holds longer than this are cut
+const MAX_DUCK_SECS: f32 = 60.0;
+
+impl DuckConfig {
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        if !(0.0..=1.0).contains(&self.depth) {
+            let depth = self.depth;
+            self.depth = default_duck_depth();
+            warnings.push(format!(
+                "duck depth {} is not between 0 and 1, using {}",
+                depth, self.depth
+            ));
+        }
+        check_secs(
+            "duck fade_secs",
+            &mut self.fade_secs,
+            default_duck_fade_secs(),
+            MAX_DUCK_SECS,
+            warnings,
+        );
+        check_secs(
+            "duck hold_secs",
+            &mut self.hold_secs,
+            default_duck_hold_secs(),
+            MAX_DUCK_SECS,
+            warnings,
+        );
+    }
+}
+
+impl Default for DuckConfig {
+    fn default() -> Self {
+        Self {
+            depth: default_duck_depth(),
+            hold_secs: default_duck_hold_secs(),
+            fade_secs: default_duck_fade_secs(),
+            curve: DuckCurve::default(),
+        }
+    }
+}
+
+/// Evens out loudness between stations.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoudnessConfig {
+    /// Slowly adjust the gain so every station plays at `target_db`.
+    #[serde(default)]
+    pub normalize: bool,
+    /// Target RMS level in dBFS.
+    #[serde(default = "default_loudness_target_db")]
+    pub target_db: f32,
+    /// Fixed gain per station name in dB, applied even without `normalize`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub station_gain_db: BTreeMap<String, f32>,
+}
+
+fn default_loudness_target_db() -> f32 {
+    -18.0
+}
+
+impl Default for LoudnessConfig {
+    fn default() -> Self {
+        Self {
+            normalize: false,
+            target_db: default_loudness_target_db(),
+            station_gain_db: BTreeMap::new(),
+        }
+    }
+}
+
+impl LoudnessConfig {
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        if !self.target_db.is_finite() || self.target_db > 0.0 {
+            let target = self.target_db;
+            self.target_db = default_loudness_target_db();
+            warnings.push(format!(
+                "loudness target_db {} is invalid, using {}",
+                target, self.target_db
+            ));
+        }
+        self.station_gain_db.retain(|name, db| {
+            if !db.is_finite() {
+                warnings.push(format!(
+                    "loudness gain {} for {} is invalid, ignoring it",
+                    db, name
+                ));
+            }
+            db.is_finite()
+        });
+    }
+}
+
+/// Pomodoro timer lengths.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PomodoroConfig {
+    #[serde(default)]
+    pub preset: Preset,
+    /// Focus length for the custom preset, in minutes.
+    #[serde(default = "default_focus_mins")]
+    pub focus_mins: u32,
+    /// Break length for the custom preset, in minutes.
+    #[serde(default = "default_break_mins")]
+    pub break_mins: u32,
+    /// Focus sessions before a long break. 0 turns long breaks off.
+    #[serde(default = "default_long_break_every")]
+    pub long_break_every: u32,
+    #[serde(default = "default_long_break_mins")]
+    pub long_break_mins: u32,
+    /// Start breaks without waiting for space.
+    #[serde(default)]
+    pub auto_start_breaks: bool,
+    /// Start focus sessions without waiting for space.
+    #[serde(default)]
+    pub auto_start_focus: bool,
+    /// Grace period before an auto-start, during which any key holds the
+    /// timer. 0 starts right away.
+    #[serde(default = "default_auto_start_countdown_secs")]
+    pub auto_start_countdown_secs: u32,
+}
+
+fn default_focus_mins() -> u32 {
+    25
+}
+
+fn default_break_mins() -> u32 {
+    5
+}
+
+fn default_long_break_every() -> u32 {
+    4
+}
+
+fn default_long_break_mins() -> u32 {
+    15
+}
+
+fn default_auto_start_countdown_secs() -> u32 {
+    10
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            preset: Preset::default(),
+            focus_mins: default_focus_mins(),
+            break_mins: default_break_mins(),
+            long_break_every: default_long_break_every(),
+            long_break_mins: default_long_break_mins(),
+            auto_start_breaks: false,
+            auto_start_focus: false,
+            auto_start_countdown_secs: default_auto_start_countdown_secs(),
+        }
+    }
+}
+
+impl PomodoroConfig {
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        // Checked whatever the preset, `P` can switch to custom later
+        for (name, mins, default) in [
+            ("focus_mins", &mut self.focus_mins, default_focus_mins()),
+            ("break_mins", &mut self.break_mins, default_break_mins()),
+            (
+                "long_break_mins",
+                &mut self.long_break_mins,
+                default_long_break_mins(),
+            ),
+        ] {
+            if let Err(e) = pomodoro::check_minutes(name, *mins) {
+                *mins = default;
+                warnings.push(format!("{}, using {}", e, default));
+            }
+        }
+    }
+}
+
+/// An ambient noise layer in the mixer.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LayerConfig {
+    pub noise: Noise,
+    #[serde(default = "default_layer_volume")]
+    pub volume: u8,
+    #[serde(default)]
+    pub muted: bool,
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+fn default_layer_volume() -> u8 {
+    30
+}
+
+fn default_layers() -> Vec<LayerConfig> {
+    Noise::ALL
+        .into_iter()
+        .map(|noise| LayerConfig {
+            noise,
+            volume: default_layer_volume(),
+            muted: false,
+            enabled: false,
+        })
+        .collect()
+}
+
+/// Where the last run left off, restored on the next launch.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Session {
+    /// Station name, so reordering the station list doesn't matter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub station: Option<String>,
+    #[serde(default = "default_volume")]
+    pub volume: u8,
+    #[serde(default)]
+    pub muted: bool,
+    #[serde(default)]
+    pub show_todo: bool,
+    #[serde(default = "default_show_pomodoro")]
+    pub show_pomodoro: bool,
+    #[serde(default)]
+    pub show_visualizer: bool,
+}
+
+fn default_volume() -> u8 {
+    100
+}
+
+fn default_show_pomodoro() -> bool {
+    true
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            station: None,
+            volume: default_volume(),
+            muted: false,
+            show_todo: false,
+            show_pomodoro: default_show_pomodoro(),
+            show_visualizer: false,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub theme: ThemeName,
+    /// Parsed one by one in `radio::load_stations`, so a bad entry is
+    /// reported instead of failing the whole config.
+    #[serde(default)]
+    pub stations: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub station_mode: StationMode,
+    #[serde(default)]
+    pub radio: RadioConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub duck: DuckConfig,
+    #[serde(default)]
+    pub loudness: LoudnessConfig,
+    #[serde(default)]
+    pub pomodoro: PomodoroConfig,
+    #[serde(default = "default_layers")]
+    pub layers: Vec<LayerConfig>,
+    /// Device name, `null` or `file:<path>`. Overridden by `--output`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_device: Option<String>,
+    #[serde(default)]
+    pub session: Session,
+    /// Station catalog for the browser: an http(s) url, `file://` url or path.
+    #[serde(default = "default_catalog_url")]
+    pub catalog_url: String,
+}
+
+fn default_catalog_url() -> String {
+    "https://somafm.com/channels.json".to_string()
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             theme: ThemeName::default(),
+            stations: Vec::new(),
+            station_mode: StationMode::default(),
+            radio: RadioConfig::default(),
+            network: NetworkConfig::default(),
+            duck: DuckConfig::default(),
+            loudness: LoudnessConfig::default(),
+            pomodoro: PomodoroConfig::default(),
+            layers: default_layers(),
+            output_device: None,
+            session: Session::default(),
+            catalog_url: default_catalog_url(),
         }
     }
 }
 
+impl Config {
+    /// Swaps settings that would break playback for safe values, and
+    /// describes each change.
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        self.radio.validate(&mut warnings);
+        self.duck.validate(&mut warnings);
+        self.network.validate(&mut warnings);
+        self.loudness.validate(&mut warnings);
+        self.pomodoro.validate(&mut warnings);
+        warnings
+    }
+}
+
 fn data_dir() -> PathBuf {
     let base = dirs::data_dir().unwrap_or_else(|| PathBuf::from("."));
     base.join("loshell")
@@ -35,6 +518,26 @@ pub fn get_data_path() -> PathBuf {
     data_dir().join("tasks.json")
 }
 
+pub fn recordings_dir() -> PathBuf {
+    data_dir().join("recordings")
+}
+
+pub fn catalog_cache_path() -> PathBuf {
+    data_dir().join("catalog.json")
+}
+
+pub fn exports_dir() -> PathBuf {
+    data_dir().join("exports")
+}
+
+pub fn history_path() -> PathBuf {
+    data_dir().join("history.json")
+}
+
+fn sessions_path() -> PathBuf {
+    data_dir().join("sessions.jsonl")
+}
+
 fn config_path() -> PathBuf {
     data_dir().join("config.json")
 }
@@ -57,20 +560,210 @@ pub fn save_tasks(data: &TaskData) {
     }
 }
 
-pub fn load_config() -> Config {
-    let path = config_path();
-    match fs::read_to_string(&path) {
+pub fn load_history(path: &Path) -> HistoryData {
+    match fs::read_to_string(path) {
         Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-        Err(_) => Config::default(),
+        Err(_) => HistoryData::default(),
+    }
+}
+
+pub fn save_history(path: &Path, data: &HistoryData) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string(data) {
+        let _ = fs::write(path, json);
+    }
+}
+
+/// Every logged pomodoro session, oldest first. Unreadable lines are skipped.
+pub fn load_sessions() -> Vec<SessionEntry> {
+    match fs::read_to_string(sessions_path()) {
+        Ok(content) => content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn append_session(entry: &SessionEntry) {
+    let path = sessions_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string(entry)
+        && let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path)
+    {
+        let _ = writeln!(file, "{}", json);
     }
 }
 
+/// The saved config, or the default one if there is none yet. A config that
+/// can't be read or parsed is an error, so it can be reported.
+pub fn load_config() -> Result<Config, String> {
+    read_config(&config_path())
+}
+
+fn read_config(path: &Path) -> Result<Config, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Writes the config. A config file that doesn't parse is moved to
+/// `config.json.bak` first, so the user's settings aren't lost.
 pub fn save_config(config: &Config) {
-    let path = config_path();
+    write_config(&config_path(), config);
+}
+
+fn write_config(path: &Path, config: &Config) {
+    if let Ok(content) = fs::read_to_string(path)
+        && serde_json::from_str::<Config>(&content).is_err()
+        && fs::rename(path, path.with_extension("json.bak")).is_err()
+    {
+        return;
+    }
     if let Some(parent) = path.parent() {
         let _ = fs::create_dir_all(parent);
     }
     if let Ok(json) = serde_json::to_string_pretty(config) {
-        let _ = fs::write(&path, json);
+        let _ = fs::write(path, json);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(json: &str) -> (Config, Vec<String>) {
+        let mut config: Config = serde_json::from_str(json).unwrap();
+        let warnings = config.validate();
+        (config, warnings)
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(Config::default().validate().is_empty());
+    }
+
+    #[test]
+    fn crossfade_is_kept_in_range() {
+        // Too big for an f32, so it parses as infinity
+        let (config, warnings) = load(r#"{"radio": {"crossfade_secs": 1e39}}"#);
+        assert_eq!(config.radio.crossfade_secs, default_crossfade_secs());
+        assert_eq!(warnings.len(), 1);
+
+        let (config, _) = load(r#"{"radio": {"crossfade_secs": -1}}"#);
+        assert_eq!(config.radio.crossfade_secs, default_crossfade_secs());
+
+        let (config, warnings) = load(r#"{"radio": {"crossfade_secs": 600}}"#);
+        assert_eq!(config.radio.crossfade_secs, MAX_CROSSFADE_SECS);
+        assert_eq!(warnings.len(), 1);
+
+        let (config, warnings) = load(r#"{"radio": {"crossfade_secs": 0}}"#);
+        assert_eq!(config.radio.crossfade_secs, 0.0);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn duck_settings_are_kept_in_range() {
+        let (config, warnings) =
+            load(r#"{"duck": {"depth": 1.5, "fade_secs": 1e39, "hold_secs": -2}}"#);
+        assert_eq!(config.duck.depth, default_duck_depth());
+        assert_eq!(config.duck.fade_secs, default_duck_fade_secs());
+        assert_eq!(config.duck.hold_secs, default_duck_hold_secs());
+        assert_eq!(warnings.len(), 3);
+
+        let (config, warnings) = load(r#"{"duck": {"depth": 0, "hold_secs": 3600}}"#);
+        assert_eq!(config.duck.depth, 0.0);
+        assert_eq!(config.duck.hold_secs, MAX_DUCK_SECS);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn pomodoro_lengths_are_kept_in_range() {
+        let (config, warnings) = load(
+            r#"{"pomodoro": {"preset": "custom", "focus_mins": 0, "break_mins": 600, "long_break_mins": 0}}"#,
+        );
+        assert_eq!(config.pomodoro.focus_mins, default_focus_mins());
+        assert_eq!(config.pomodoro.break_mins, default_break_mins());
+        assert_eq!(config.pomodoro.long_break_mins, default_long_break_mins());
+        assert_eq!(
+            warnings[0],
+            "pomodoro focus_mins must be at least 1 minute, using 25"
+        );
+        assert_eq!(warnings.len(), 3);
+
+        let (config, warnings) = load(r#"{"pomodoro": {"focus_mins": 90, "break_mins": 1}}"#);
+        assert_eq!(config.pomodoro.focus_mins, 90);
+        assert_eq!(config.pomodoro.break_mins, 1);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn loudness_levels_must_be_finite() {
+        let (config, warnings) =
+            load(r#"{"loudness": {"target_db": 1e39, "station_gain_db": {"a": -1e39, "b": 3}}}"#);
+        assert_eq!(config.loudness.target_db, default_loudness_target_db());
+        assert_eq!(config.loudness.station_gain_db.len(), 1);
+        assert_eq!(config.loudness.station_gain_db["b"], 3.0);
+        assert_eq!(warnings.len(), 2);
+
+        let (config, warnings) = load(r#"{"loudness": {"target_db": 6}}"#);
+        assert_eq!(config.loudness.target_db, default_loudness_target_db());
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn zero_timeouts_are_replaced() {
+        let (config, warnings) =
+            load(r#"{"network": {"connect_timeout_secs": 0, "read_timeout_secs": 0}}"#);
+        assert_eq!(
+            config.network.connect_timeout_secs,
+            default_connect_timeout_secs()
+        );
+        assert_eq!(
+            config.network.read_timeout_secs,
+            default_read_timeout_secs()
+        );
+        assert_eq!(warnings.len(), 2);
+
+        let (config, warnings) = load(r#"{"network": {"read_timeout_secs": 60}}"#);
+        assert_eq!(config.network.read_timeout_secs, 60);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn a_broken_config_is_reported_and_backed_up() {
+        let dir = std::env::temp_dir().join(format!("loshell-config-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("config.json");
+        assert!(read_config(&path).is_ok());
+
+        let broken = r#"{"theme": "nope", "catalog_url": "file:///mine.json"}"#;
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, broken).unwrap();
+        let error = read_config(&path).err().unwrap();
+        assert!(error.contains("config.json"), "{}", error);
+
+        write_config(&path, &Config::default());
+        let backup = dir.join("config.json.bak");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), broken);
+        assert!(read_config(&path).is_ok());
+
+        // Once the file parses again it is simply overwritten
+        let config = Config {
+            catalog_url: "file:///other.json".to_string(),
+            ..Default::default()
+        };
+        write_config(&path, &config);
+        assert_eq!(read_config(&path).unwrap().catalog_url, config.catalog_url);
+        assert_eq!(fs::read_to_string(&backup).unwrap(), broken);
+        let _ = fs::remove_dir_all(&dir);
     }
 }
@@ -0,0 +1,219 @@
+//! Test helpers: a tiny HTTP server on localhost and generated audio.
+
+use std::f32::consts::TAU;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use native_tls::{Identity, TlsAcceptor};
+use ogg::{PacketWriteEndInfo, PacketWriter};
+use ropus::{Application, Channels, Encoder};
+
+pub const WAV_RATE: u32 = 8000;
+
+/// The first line and headers of a request, up to the blank line.
+pub struct Request {
+    pub head: String,
+}
+
+impl Request {
+    /// The request target, e.g. `/stream` or, through a proxy, `http://host/stream`.
+    pub fn target(&self) -> &str {
+        self.head.split_whitespace().nth(1).unwrap_or("")
+    }
+}
+
+/// Serves each connection on its own thread until the test process exits.
+pub fn serve<F>(handler: F) -> SocketAddr
+where
+    F: Fn(Request, TcpStream) + Send + Sync + Clone + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handler = handler.clone();
+            thread::spawn(move || {
+                if let Some(request) = read_request(&stream) {
+                    handler(request, stream);
+                }
+            });
+        }
+    });
+    addr
+}
+
+fn read_request(stream: &TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+        head.push_str(&line);
+    }
+    Some(Request { head })
+}
+
+/// The self-signed certificate `serve_tls` uses, for `localhost` and 127.0.0.1.
+pub fn tls_cert_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/localhost.pem")
+}
+
+/// Answers every request with `body` over TLS, until the test process exits.
+pub fn serve_tls(body: &'static [u8]) -> SocketAddr {
+    let cert = include_bytes!("../tests/fixtures/localhost.pem");
+    let key = include_bytes!("../tests/fixtures/localhost.key");
+    let acceptor = TlsAcceptor::new(Identity::from_pkcs8(cert, key).unwrap()).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let acceptor = acceptor.clone();
+            thread::spawn(move || {
+                // Clients that don't trust the certificate hang up here
+                let Ok(mut stream) = acceptor.accept(stream) else {
+                    return;
+                };
+                let mut reader = BufReader::new(&mut stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+                    line.clear();
+                }
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(body);
+            });
+        }
+    });
+    addr
+}
+
+/// An address nothing listens on, so connecting is refused.
+pub fn dead_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+/// Writes a complete `200 OK` response.
+pub fn respond(stream: &mut TcpStream, headers: &[(&str, &str)], body: &[u8]) {
+    let mut head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n", body.len());
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(body);
+}
+
+/// A mono 16-bit WAV file of a quiet sine wave.
+pub fn wav(secs: f32) -> Vec<u8> {
+    let frames = (secs * WAV_RATE as f32) as u32;
+    let mut out = wav_header(frames * 2);
+    for n in 0..frames {
+        let t = n as f32 / WAV_RATE as f32;
+        let sample = ((t * 440.0 * TAU).sin() * 0.25 * i16::MAX as f32) as i16;
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    out
+}
+
+fn wav_header(data_len: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(44);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // mono
+    out.extend_from_slice(&WAV_RATE.to_le_bytes());
+    out.extend_from_slice(&(WAV_RATE * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    out
+}
+
+pub const OPUS_PRE_SKIP: u16 = 312;
+
+/// An Ogg Opus stream of a quiet sine wave, made of `songs` chained
+/// logical streams of `secs` each, like a radio server sends.
+pub fn opus(secs: f32, channels: u8, songs: u32) -> Vec<u8> {
+    const FRAME: usize = 960;
+    let layout = match channels {
+        1 => Channels::Mono,
+        _ => Channels::Stereo,
+    };
+    let mut writer = PacketWriter::new(Vec::new());
+    for serial in 1..=songs {
+        let mut encoder = Encoder::builder(48_000, layout, Application::Audio)
+            .build()
+            .unwrap();
+        let mut head = b"OpusHead".to_vec();
+        head.push(1);
+        head.push(channels);
+        head.extend_from_slice(&OPUS_PRE_SKIP.to_le_bytes());
+        head.extend_from_slice(&48_000u32.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes());
+        head.push(0);
+        writer
+            .write_packet(head, serial, PacketWriteEndInfo::EndPage, 0)
+            .unwrap();
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend_from_slice(&0u32.to_le_bytes());
+        tags.extend_from_slice(&0u32.to_le_bytes());
+        writer
+            .write_packet(tags, serial, PacketWriteEndInfo::EndPage, 0)
+            .unwrap();
+
+        let frames = (secs * 48_000.0) as usize / FRAME;
+        for i in 0..frames {
+            let pcm: Vec<i16> = (0..FRAME)
+                .flat_map(|n| {
+                    let t = (i * FRAME + n) as f32 / 48_000.0;
+                    let sample = ((t * 440.0 * TAU).sin() * 0.25 * i16::MAX as f32) as i16;
+                    std::iter::repeat_n(sample, channels as usize)
+                })
+                .collect();
+            let mut packet = vec![0u8; 4000];
+            let len = encoder.encode(&pcm, &mut packet).unwrap();
+            packet.truncate(len);
+            let end = if i + 1 == frames {
+                PacketWriteEndInfo::EndStream
+            } else {
+                PacketWriteEndInfo::NormalPacket
+            };
+            let granule = ((i + 1) * FRAME) as u64;
+            writer.write_packet(packet, serial, end, granule).unwrap();
+        }
+    }
+    writer.into_inner()
+}
+
+/// Audio bytes with a metadata block after every `metaint` of them.
+pub fn icy(audio: &[u8], metaint: usize, blocks: &[&str]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (i, chunk) in audio.chunks(metaint).enumerate() {
+        out.extend_from_slice(chunk);
+        if chunk.len() < metaint {
+            break;
+        }
+        let text = blocks.get(i).copied().unwrap_or("");
+        let len = text.len().div_ceil(16);
+        out.push(len as u8);
+        out.extend_from_slice(text.as_bytes());
+        out.resize(out.len() + len * 16 - text.len(), 0);
+    }
+    out
+}
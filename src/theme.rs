@@ -1,8 +1,9 @@
 use ratatui::style::{Color, Modifier, Style};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ThemeName {
+    #[default]
     Bladerunner,
     CatppuccinMocha,
     Gruvbox,
@@ -38,12 +39,6 @@ impl ThemeName {
     }
 }
 
-impl Default for ThemeName {
-    fn default() -> Self {
-        ThemeName::Bladerunner
-    }
-}
-
 pub struct Theme {
     pub bg: Color,
     pub fg: Color,
@@ -0,0 +1,78 @@
+use ratatui::{
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+};
+
+use crate::theme::Theme;
+
+/// Every key of the main screen, shown with `?`. Overlays list their own.
+const KEYS: &[(&str, &str)] = &[
+    ("s", "play/stop radio"),
+    ("←/→", "switch station"),
+    ("</>", "previous/next track"),
+    ("[/]", "volume of the mix channel"),
+    ("{/}", "station gain"),
+    ("N", "loudness normalization"),
+    ("tab", "select mix channel"),
+    ("m", "mute channel"),
+    ("o", "ambient layer on/off"),
+    ("R", "record the stream"),
+    ("z", "sleep timer"),
+    ("e", "radio error details"),
+    ("b", "browse the catalog"),
+    ("f", "star the playing song"),
+    ("h", "history, / search, c/x export"),
+    ("S", "pomodoro stats"),
+    ("space", "start/pause timer"),
+    ("r", "reset timer"),
+    ("+", "add 5 minutes"),
+    ("P", "next pomodoro preset"),
+    ("p", "show/hide pomodoro"),
+    ("t", "todo list"),
+    ("n", "new task"),
+    ("j/k", "move between tasks"),
+    ("x", "mark task done"),
+    ("d", "delete task"),
+    ("enter", "track task with pomodoro"),
+    ("v", "spectrum visualizer"),
+    ("T", "next theme"),
+    ("q", "quit"),
+];
+
+const KEY_WIDTH: usize = 7;
+const COLUMN_WIDTH: usize = 38;
+
+/// Two columns of keys; any key closes it.
+pub fn keys<'a>(theme: &Theme) -> Paragraph<'a> {
+    let rows = KEYS.len().div_ceil(2);
+    let (left, right) = KEYS.split_at(rows);
+    let lines: Vec<Line> = (0..rows)
+        .map(|i| {
+            let mut spans = Vec::new();
+            for (column, entries) in [left, right].iter().enumerate() {
+                if let Some((key, action)) = entries.get(i) {
+                    spans.push(Span::styled(format!("{:<KEY_WIDTH$}", key), theme.accent()));
+                    let width = if column == 0 {
+                        COLUMN_WIDTH - KEY_WIDTH
+                    } else {
+                        0
+                    };
+                    spans.push(Span::styled(format!("{:<width$}", action), theme.base()));
+                }
+            }
+            Line::from(spans)
+        })
+        .collect();
+
+    Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(Span::styled(" keys ", theme.title()))
+                .borders(Borders::ALL)
+                .border_style(theme.frame()),
+        )
+        .style(theme.base())
+}
+
+pub const KEYS_WIDTH: u16 = (COLUMN_WIDTH * 2 + 2) as u16;
+pub const KEYS_HEIGHT: u16 = (KEYS.len().div_ceil(2) + 2) as u16;
@@ -0,0 +1,336 @@
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::Style,
+    text::{Line, Span},
+    widgets::Paragraph,
+};
+use rodio::{ChannelCount, SampleRate, Source, source::SeekError};
+
+use crate::theme::Theme;
+
+const FFT_SIZE: usize = 1024;
+const BANDS: usize = 32;
+const MIN_FREQ: f32 = 40.0;
+const MAX_FREQ: f32 = 16000.0;
+// Band levels below FLOOR_DB read as empty, above CEIL_DB as full
+const FLOOR_DB: f32 = -72.0;
+const CEIL_DB: f32 = -12.0;
+// How far a bar may drop per tick, so peaks fall smoothly
+const FALLOFF: f32 = 0.08;
+
+const EIGHTHS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The most recent decoded audio, mixed down to mono.
+#[derive(Clone, Default)]
+pub struct Scope {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+/// Passes samples through untouched while copying them into a `Scope`.
+pub struct Tap<S> {
+    input: S,
+    scope: Arc<Mutex<Scope>>,
+    buffer: Vec<f32>,
+    frame_sum: f32,
+    frame_len: u16,
+}
+
+impl<S: Source> Tap<S> {
+    pub fn new(input: S, scope: Arc<Mutex<Scope>>) -> Self {
+        Self {
+            input,
+            scope,
+            buffer: Vec::with_capacity(FFT_SIZE),
+            frame_sum: 0.0,
+            frame_len: 0,
+        }
+    }
+
+    fn push(&mut self, sample: f32) {
+        self.frame_sum += sample;
+        self.frame_len += 1;
+        if self.frame_len < self.input.channels().max(1) {
+            return;
+        }
+
+        self.buffer.push(self.frame_sum / self.frame_len as f32);
+        self.frame_sum = 0.0;
+        self.frame_len = 0;
+
+        // Hand over whole windows so the audio thread only locks now and then
+        if self.buffer.len() == FFT_SIZE {
+            let mut scope = self.scope.lock().unwrap();
+            std::mem::swap(&mut scope.samples, &mut self.buffer);
+            scope.sample_rate = self.input.sample_rate();
+            self.buffer.clear();
+        }
+    }
+}
+
+impl<S: Source> Iterator for Tap<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        self.push(sample);
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S: Source> Source for Tap<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.input.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
+
+/// Spectrum bars for the center of the screen.
+pub struct Visualizer {
+    pub visible: bool,
+    levels: [f32; BANDS],
+}
+
+impl Visualizer {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            levels: [0.0; BANDS],
+        }
+    }
+
+    pub fn toggle_visible(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Analyzes the latest audio. Called once per UI tick; with nothing
+    /// playing the bars fall back to zero.
+    pub fn update(&mut self, scope: Option<Scope>) {
+        let bands = match scope {
+            Some(scope) if scope.samples.len() == FFT_SIZE && scope.sample_rate > 0 => {
+                spectrum(&scope.samples, scope.sample_rate)
+            }
+            _ => [0.0; BANDS],
+        };
+        for (level, band) in self.levels.iter_mut().zip(bands) {
+            *level = band.max(*level - FALLOFF);
+        }
+    }
+
+    pub fn draw(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        if !self.visible || area.width < BANDS as u16 || area.height == 0 {
+            return;
+        }
+
+        let bar_width = (area.width as usize / BANDS).max(1);
+        let height = area.height as usize;
+        let lines: Vec<Line> = (0..height)
+            .map(|row| {
+                // Rows counted from the bottom, each worth eight steps
+                let from_bottom = height - 1 - row;
+                let style = if from_bottom * 3 >= height * 2 {
+                    theme.hot()
+                } else if from_bottom * 3 >= height {
+                    Style::default().fg(theme.secondary).bg(theme.bg)
+                } else {
+                    theme.accent()
+                };
+                let cells: String = self
+                    .levels
+                    .iter()
+                    .map(|level| {
+                        let steps = (level * (height * 8) as f32) as usize;
+                        let fill = steps.saturating_sub(from_bottom * 8).min(8);
+                        let ch = match fill {
+                            0 => ' ',
+                            n => EIGHTHS[n - 1],
+                        };
+                        // Leave a gap between bars when there's room
+                        let mut cell = ch.to_string().repeat(bar_width.max(2) - 1);
+                        if bar_width >= 2 {
+                            cell.push(' ');
+                        }
+                        cell
+                    })
+                    .collect();
+                Line::from(Span::styled(cells, style))
+            })
+            .collect();
+
+        f.render_widget(Paragraph::new(lines).style(theme.base()), area);
+    }
+}
+
+/// Level of each band between 0.0 and 1.0, on log-spaced frequencies.
+fn spectrum(samples: &[f32], sample_rate: u32) -> [f32; BANDS] {
+    // Hann window to keep strong bands from smearing into their neighbours
+    let mut re: Vec<f32> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| s * (0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos()))
+        .collect();
+    let mut im = vec![0.0; FFT_SIZE];
+    fft(&mut re, &mut im);
+
+    // A full-scale sine through the window peaks at N/4
+    let scale = 4.0 / FFT_SIZE as f32;
+    let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+    let max_freq = MAX_FREQ.min(sample_rate as f32 / 2.0);
+    if max_freq <= MIN_FREQ {
+        // Nothing in the range the bands cover
+        return [0.0; BANDS];
+    }
+    let ratio = (max_freq / MIN_FREQ).powf(1.0 / BANDS as f32);
+
+    let mut bands = [0.0; BANDS];
+    for (i, band) in bands.iter_mut().enumerate() {
+        let low = MIN_FREQ * ratio.powi(i as i32);
+        let high = low * ratio;
+        let first = ((low / bin_hz) as usize).clamp(1, FFT_SIZE / 2 - 1);
+        let last = ((high / bin_hz) as usize).clamp(first, FFT_SIZE / 2 - 1);
+
+        let peak = (first..=last)
+            .map(|k| (re[k] * re[k] + im[k] * im[k]).sqrt() * scale)
+            .fold(0.0, f32::max);
+        let db = 20.0 * peak.max(1e-9).log10();
+        *band = ((db - FLOOR_DB) / (CEIL_DB - FLOOR_DB)).clamp(0.0, 1.0);
+    }
+    bands
+}
+
+/// In-place iterative radix-2 FFT. The length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let tre = re[b] * cos - im[b] * sin;
+                let tim = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - tre;
+                im[b] = im[a] - tim;
+                re[a] += tre;
+                im[a] += tim;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, sample_rate: u32) -> Vec<f32> {
+        (0..FFT_SIZE)
+            .map(|n| (2.0 * PI * freq * n as f32 / sample_rate as f32).sin() * 0.5)
+            .collect()
+    }
+
+    /// The middle frequency of a band, on the same scale as `spectrum`.
+    fn band_center(band: usize, sample_rate: u32) -> f32 {
+        let max_freq = MAX_FREQ.min(sample_rate as f32 / 2.0);
+        let ratio = (max_freq / MIN_FREQ).powf(1.0 / BANDS as f32);
+        MIN_FREQ * ratio.powf(band as f32 + 0.5)
+    }
+
+    fn loudest(bands: &[f32; BANDS]) -> usize {
+        (0..BANDS)
+            .max_by(|&a, &b| bands[a].total_cmp(&bands[b]))
+            .unwrap()
+    }
+
+    #[test]
+    fn fft_finds_a_single_frequency() {
+        let mut re: Vec<f32> = (0..FFT_SIZE)
+            .map(|n| (2.0 * PI * 8.0 * n as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        let mut im = vec![0.0; FFT_SIZE];
+        fft(&mut re, &mut im);
+        for k in 0..FFT_SIZE {
+            let magnitude = (re[k] * re[k] + im[k] * im[k]).sqrt();
+            let expected = if k == 8 || k == FFT_SIZE - 8 {
+                FFT_SIZE as f32 / 2.0
+            } else {
+                0.0
+            };
+            assert!(
+                (magnitude - expected).abs() < 0.01,
+                "bin {}: {}",
+                k,
+                magnitude
+            );
+        }
+    }
+
+    #[test]
+    fn a_sine_peaks_in_its_band() {
+        for (sample_rate, band) in [(44_100, 16), (44_100, 28), (8_000, 12), (8_000, 25)] {
+            let freq = band_center(band, sample_rate);
+            let bands = spectrum(&sine(freq, sample_rate), sample_rate);
+            assert_eq!(loudest(&bands), band, "{} Hz at {}", freq, sample_rate);
+            assert!(bands[band] > 0.9, "{}", bands[band]);
+            // Far away bands stay quiet
+            assert!(bands[0] < 0.2 && bands[BANDS - 1] < 0.2, "{:?}", bands);
+        }
+    }
+
+    #[test]
+    fn silence_is_flat() {
+        for sample_rate in [8_000, 44_100] {
+            assert_eq!(spectrum(&[0.0; FFT_SIZE], sample_rate), [0.0; BANDS]);
+        }
+    }
+
+    #[test]
+    fn low_sample_rates_do_not_panic() {
+        for sample_rate in [1, 8, 79, 80, 81, 100, 1000] {
+            let bands = spectrum(&sine(20.0, sample_rate), sample_rate);
+            assert!(bands.iter().all(|b| (0.0..=1.0).contains(b)));
+        }
+    }
+}